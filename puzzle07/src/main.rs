@@ -1,4 +1,11 @@
 fn main() {
+    // `puzzle07 tree|du [depth]` or `puzzle07 largest [count]` reports on the input instead
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(report) = seven::report(INPUT, &args) {
+        print!("{report}");
+        return;
+    }
+
     for foo in [seven::part1, seven::part2] {
         let start = std::time::Instant::now();
         let res = foo(INPUT);
@@ -22,8 +29,25 @@ mod seven {
         const TARGET: u64 = 30000000;
 
         let slash = parse_history(input);
-        let required = TARGET - (TOTAL - slash.size());
-        slash.dir_sizes().filter(|sz| *sz > required).min().unwrap()
+        slash.smallest_dir_freeing(TOTAL, TARGET).unwrap().1
+    }
+
+    pub fn report(input: &str, args: &[String]) -> Option<String> {
+        let (cmd, rest) = args.split_first()?;
+        let num = |default| rest.first().and_then(|n| n.parse().ok()).unwrap_or(default);
+        let slash = parse_history(input);
+        match cmd.as_str() {
+            "tree" => Some(slash.tree(num(usize::MAX))),
+            "du" => Some(slash.du(num(usize::MAX))),
+            "largest" => Some(
+                slash
+                    .largest_files(num(10))
+                    .into_iter()
+                    .map(|(path, size)| format!("{size}\t{path}\n"))
+                    .collect(),
+            ),
+            _ => None,
+        }
     }
 
     fn parse_history(history: &str) -> Directory {
@@ -36,6 +60,13 @@ mod seven {
                             let dir = dirs.pop().unwrap();
                             dirs.last_mut().unwrap().add_subdir(dir);
                         }
+                        // back to the root, which is already there
+                        "/" => {
+                            while dirs.len() > 1 {
+                                let dir = dirs.pop().unwrap();
+                                dirs.last_mut().unwrap().add_subdir(dir);
+                            }
+                        }
                         dirname => {
                             dirs.push(Directory::new(dirname));
                         }
@@ -66,26 +97,19 @@ mod seven {
     }
 
     impl DirEntry {
-        fn size(&self) -> u64 {
-            match self {
-                DirEntry::File(_, s) => *s,
-                DirEntry::SubDir(sds) => sds.size(),
-            }
-        }
-
-        fn is_subdir(&self) -> bool {
+        fn name(&self) -> &str {
             match self {
-                DirEntry::SubDir(_) => true,
-                _ => false,
+                DirEntry::File(name, _) => name,
+                DirEntry::SubDir(sd) => &sd.name,
             }
         }
     }
 
     #[derive(Debug, Clone)]
     struct Directory {
-        #[allow(unused)]
         name: String,
         entries: Vec<DirEntry>,
+        size: u64, // total size of everything below, see add_subdir
     }
 
     impl Directory {
@@ -93,38 +117,379 @@ mod seven {
             Self {
                 name: String::from(name),
                 entries: vec![],
+                size: 0,
             }
         }
 
         fn add_file(&mut self, name: &str, size: &str) {
-            self.entries.push(DirEntry::File(
-                String::from(name),
-                u64::from_str_radix(size, 10).unwrap(),
-            ))
+            let size = size.parse::<u64>().unwrap();
+            self.size += size;
+            self.entries.push(DirEntry::File(String::from(name), size))
         }
 
+        // A directory is only added to its parent once it's closed (all of its content was
+        // listed), so sizes accumulate bottom-up in a single post-order pass over the tree.
         fn add_subdir(&mut self, dir: Directory) {
+            self.size += dir.size;
             self.entries.push(DirEntry::SubDir(dir));
         }
 
         fn size(&self) -> u64 {
-            self.entries.iter().map(DirEntry::size).sum()
+            self.size
+        }
+
+        fn dirs(&self) -> Dirs<'_> {
+            Dirs {
+                next: Some(self),
+                pending: vec![],
+            }
+        }
+
+        fn dir_sizes(&self) -> impl Iterator<Item = u64> + '_ {
+            self.dirs().map(Directory::size)
+        }
+
+        fn subdirs(&self) -> impl Iterator<Item = &Directory> {
+            self.entries.iter().filter_map(|e| match e {
+                DirEntry::SubDir(sd) => Some(sd),
+                DirEntry::File(_, _) => None,
+            })
+        }
+
+        // Visits every directory in the tree (pre-order), along with its full path and depth.
+        fn walk<'a>(
+            &'a self,
+            path: &str,
+            depth: usize,
+            visit: &mut impl FnMut(&str, usize, &'a Directory),
+        ) {
+            visit(path, depth, self);
+            for sd in self.subdirs() {
+                sd.walk(&join(path, &sd.name), depth + 1, visit);
+            }
+        }
+    }
+
+    // Pre-order iteration over a directory and all the directories below it
+    struct Dirs<'a> {
+        next: Option<&'a Directory>,
+        pending: Vec<std::slice::Iter<'a, DirEntry>>,
+    }
+
+    impl<'a> Iterator for Dirs<'a> {
+        type Item = &'a Directory;
+
+        fn next(&mut self) -> Option<Self::Item> {
+            if let Some(dir) = self.next.take() {
+                self.pending.push(dir.entries.iter());
+                return Some(dir);
+            }
+            while let Some(entries) = self.pending.last_mut() {
+                match entries.next() {
+                    Some(DirEntry::SubDir(sd)) => {
+                        self.pending.push(sd.entries.iter());
+                        return Some(sd);
+                    }
+                    Some(DirEntry::File(_, _)) => (),
+                    None => {
+                        self.pending.pop();
+                    }
+                }
+            }
+            None
         }
+    }
 
-        fn dir_sizes(&self) -> Box<dyn Iterator<Item = u64>> {
+    // Queries over the whole tree, reporting the full path of what they find.
+    impl Directory {
+        fn find_dirs(&self, pred: impl Fn(u64) -> bool) -> Vec<(String, u64)> {
+            let mut found = vec![];
+            self.walk(&self.name, 0, &mut |path, _, dir| {
+                let size = dir.size();
+                if pred(size) {
+                    found.push((String::from(path), size));
+                }
+            });
+            found
+        }
+
+        // The smallest directory whose deletion leaves more than `needed` free out of `total`.
+        fn smallest_dir_freeing(&self, total: u64, needed: u64) -> Option<(String, u64)> {
+            let required = needed.saturating_sub(total.saturating_sub(self.size()));
+            self.find_dirs(|sz| sz > required)
+                .into_iter()
+                .min_by_key(|(_, sz)| *sz)
+        }
+
+        fn largest_files(&self, n: usize) -> Vec<(String, u64)> {
+            let mut files = vec![];
+            self.walk(&self.name, 0, &mut |path, _, dir| {
+                for e in &dir.entries {
+                    if let DirEntry::File(name, size) = e {
+                        files.push((join(path, name), *size));
+                    }
+                }
+            });
+            files.sort_by(|(p1, s1), (p2, s2)| s2.cmp(s1).then_with(|| p1.cmp(p2)));
+            files.truncate(n);
+            files
+        }
+
+        // `tree`-like listing, in the same format as the puzzle's description, down to `max_depth`
+        // levels below this directory.
+        fn tree(&self, max_depth: usize) -> String {
+            let mut out = String::new();
+            self.tree_into(&mut out, 0, max_depth);
+            out
+        }
+
+        fn tree_into(&self, out: &mut String, depth: usize, max_depth: usize) {
+            let indent = "  ".repeat(depth);
+            out.push_str(&format!(
+                "{indent}- {} (dir, size={})\n",
+                self.name,
+                self.size()
+            ));
+            if depth == max_depth {
+                return;
+            }
+            let mut entries: Vec<_> = self.entries.iter().collect();
+            entries.sort_by_key(|e| e.name());
+            for e in entries {
+                match e {
+                    DirEntry::SubDir(sd) => sd.tree_into(out, depth + 1, max_depth),
+                    DirEntry::File(name, size) => {
+                        out.push_str(&format!("{indent}  - {name} (file, size={size})\n"))
+                    }
+                }
+            }
+        }
+
+        // `du -h --max-depth` like report: sub-directories are listed before their parent.
+        fn du(&self, max_depth: usize) -> String {
+            let mut lines = vec![];
+            self.walk(&self.name, 0, &mut |path, depth, dir| {
+                if depth <= max_depth {
+                    lines.push((depth, format!("{}\t{path}", human_size(dir.size()))));
+                }
+            });
+            // pre-order to post-order: a directory goes after everything that follows it and is deeper
+            let mut out = String::new();
+            let mut pending: Vec<(usize, String)> = vec![];
+            for (depth, line) in lines {
+                while matches!(pending.last(), Some((d, _)) if *d >= depth) {
+                    out.push_str(&pending.pop().unwrap().1);
+                    out.push('\n');
+                }
+                pending.push((depth, line));
+            }
+            while let Some((_, line)) = pending.pop() {
+                out.push_str(&line);
+                out.push('\n');
+            }
+            out
+        }
+    }
+
+    fn join(dir: &str, name: &str) -> String {
+        if dir.ends_with('/') {
+            format!("{dir}{name}")
+        } else {
+            format!("{dir}/{name}")
+        }
+    }
+
+    // Sizes the way `du -h` prints them: powers of 1024, rounded up, one decimal below 10.
+    fn human_size(size: u64) -> String {
+        const UNITS: [&str; 5] = ["K", "M", "G", "T", "P"];
+        if size < 1024 {
+            return size.to_string();
+        }
+        let mut val = size as f64;
+        let mut unit = "";
+        for u in UNITS {
+            val /= 1024.0;
+            unit = u;
+            if val.ceil() < 1024.0 {
+                break;
+            }
+        }
+        if val < 10.0 {
+            format!("{:.1}{unit}", (val * 10.0).ceil() / 10.0)
+        } else {
+            format!("{}{unit}", val.ceil())
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        const EXAMPLE: &str = "\
+$ cd /
+$ ls
+dir a
+14848514 b.txt
+8504156 c.dat
+dir d
+$ cd a
+$ ls
+dir e
+29116 f
+2557 g
+62596 h.lst
+$ cd e
+$ ls
+584 i
+$ cd ..
+$ cd ..
+$ cd d
+$ ls
+4060174 j
+8033020 d.log
+5626152 d.ext
+7214296 k";
+
+        use super::{DirEntry, Directory};
+
+        // Every level holds a file and a single sub-directory, `depth` levels down.
+        fn deep_history(depth: usize) -> String {
+            let mut history = String::from("$ cd /\n");
+            for i in 0..depth {
+                history.push_str(&format!("$ ls\n{} f{i}\ndir d{i}\n$ cd d{i}\n", i + 1));
+            }
+            history
+        }
+
+        // The original sizing: every level clones its entries and recomputes sizes from scratch.
+        fn naive_size(dir: &Directory) -> u64 {
+            dir.entries
+                .iter()
+                .map(|e| match e {
+                    DirEntry::File(_, s) => *s,
+                    DirEntry::SubDir(sd) => naive_size(sd),
+                })
+                .sum()
+        }
+
+        fn naive_dir_sizes(dir: &Directory) -> Box<dyn Iterator<Item = u64>> {
             Box::new(
-                [self.size()].into_iter().chain(
-                    self.entries
+                [naive_size(dir)].into_iter().chain(
+                    dir.entries
                         .clone()
                         .into_iter()
-                        .filter(|e| e.is_subdir())
-                        .map(|entry| match entry {
-                            DirEntry::SubDir(sd) => sd.dir_sizes(),
-                            DirEntry::File(_, _) => unreachable!(),
+                        .filter_map(|e| match e {
+                            DirEntry::SubDir(sd) => Some(naive_dir_sizes(&sd)),
+                            DirEntry::File(_, _) => None,
                         })
                         .flatten(),
                 ),
             )
         }
+
+        #[test]
+        fn deep_tree_sizes() {
+            let slash = super::parse_history(&deep_history(300));
+            assert!(slash.dir_sizes().eq(naive_dir_sizes(&slash)));
+        }
+
+        #[test]
+        fn example_part1() {
+            assert_eq!(95437, super::part1(EXAMPLE));
+        }
+
+        #[test]
+        fn real_part1() {
+            assert_eq!(1449447, super::part1(crate::INPUT));
+        }
+
+        #[test]
+        fn example_part2() {
+            assert_eq!(24933642, super::part2(EXAMPLE));
+        }
+
+        #[test]
+        fn real_part2() {
+            assert_eq!(8679207, super::part2(crate::INPUT));
+        }
+
+        #[test]
+        fn find_dirs() {
+            let slash = super::parse_history(EXAMPLE);
+            assert_eq!(
+                vec![(String::from("/a"), 94853), (String::from("/a/e"), 584)],
+                slash.find_dirs(|sz| sz <= 100000)
+            );
+        }
+
+        #[test]
+        fn smallest_dir_freeing() {
+            let slash = super::parse_history(EXAMPLE);
+            assert_eq!(
+                Some((String::from("/d"), 24933642)),
+                slash.smallest_dir_freeing(70000000, 30000000)
+            );
+            assert_eq!(None, slash.smallest_dir_freeing(70000000, 80000000));
+            // deleting /d would leave exactly what's needed, which isn't enough
+            assert_eq!(
+                Some((String::from("/"), 48381165)),
+                slash.smallest_dir_freeing(70000000, 24933642 + 21618835)
+            );
+        }
+
+        #[test]
+        fn largest_files() {
+            let slash = super::parse_history(EXAMPLE);
+            assert_eq!(
+                vec![
+                    (String::from("/b.txt"), 14848514),
+                    (String::from("/c.dat"), 8504156)
+                ],
+                slash.largest_files(2)
+            );
+        }
+
+        #[test]
+        fn tree() {
+            let slash = super::parse_history(EXAMPLE);
+            assert_eq!(
+                "\
+- / (dir, size=48381165)
+  - a (dir, size=94853)
+    - e (dir, size=584)
+    - f (file, size=29116)
+    - g (file, size=2557)
+    - h.lst (file, size=62596)
+  - b.txt (file, size=14848514)
+  - c.dat (file, size=8504156)
+  - d (dir, size=24933642)
+    - d.ext (file, size=5626152)
+    - d.log (file, size=8033020)
+    - j (file, size=4060174)
+    - k (file, size=7214296)
+",
+                slash.tree(2)
+            );
+        }
+
+        #[test]
+        fn report() {
+            let args = |a: &[&str]| a.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+            assert_eq!(None, super::report(EXAMPLE, &[]));
+            assert_eq!(None, super::report(EXAMPLE, &args(&["part1"])));
+            assert_eq!(
+                Some(String::from("14848514\t/b.txt\n")),
+                super::report(EXAMPLE, &args(&["largest", "1"]))
+            );
+            assert_eq!(
+                Some(String::from("- / (dir, size=48381165)\n")),
+                super::report(EXAMPLE, &args(&["tree", "0"]))
+            );
+        }
+
+        #[test]
+        fn du() {
+            let slash = super::parse_history(EXAMPLE);
+            assert_eq!("47M\t/\n", slash.du(0));
+            assert_eq!("584\t/a/e\n93K\t/a\n24M\t/d\n47M\t/\n", slash.du(2));
+        }
     }
 }
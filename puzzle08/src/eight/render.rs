@@ -0,0 +1,44 @@
+// Per-tree results (scores, distances, visibility as 0/1) as images, brightest = highest
+
+const RAMP: &[u8] = b" .:-=+*#%@";
+
+pub fn heatmap(grid: &[Vec<u64>]) -> String {
+    let max = max(grid);
+    let mut out = String::new();
+    for row in grid {
+        for v in row {
+            out.push(RAMP[scale(*v, max, RAMP.len() as u64 - 1) as usize] as char);
+        }
+        out.push('\n');
+    }
+    out
+}
+
+// Binary greymap (P5)
+pub fn pgm(grid: &[Vec<u64>]) -> Vec<u8> {
+    let (rows, cols) = (grid.len(), grid.first().map_or(0, Vec::len));
+    let max = max(grid);
+    let mut out = format!("P5\n{cols} {rows}\n255\n").into_bytes();
+    for row in grid {
+        out.extend(row.iter().map(|v| scale(*v, max, 255) as u8));
+    }
+    out
+}
+
+pub fn from_bools(grid: &[Vec<bool>]) -> Vec<Vec<u64>> {
+    grid.iter()
+        .map(|row| row.iter().map(|&b| b as u64).collect())
+        .collect()
+}
+
+fn max(grid: &[Vec<u64>]) -> u64 {
+    grid.iter().flatten().copied().max().unwrap_or(0)
+}
+
+fn scale(v: u64, max: u64, levels: u64) -> u64 {
+    if max == 0 {
+        0
+    } else {
+        (v as u128 * levels as u128 / max as u128) as u64
+    }
+}
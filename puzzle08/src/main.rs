@@ -1,4 +1,14 @@
+use std::io::Write;
+
 fn main() {
+    // `puzzle08 heatmap|pgm [scores|visible] [orthogonal|diagonal|knight|<rows>,<cols>]` draws
+    // the grove instead, the PGM going to stdout
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(image) = eight::report(INPUT, &args) {
+        std::io::stdout().write_all(&image).expect("can't write the image");
+        return;
+    }
+
     for foo in [eight::part1, eight::part2] {
         let start = std::time::Instant::now();
         let res = foo(INPUT);
@@ -9,87 +19,218 @@ fn main() {
 }
 
 mod eight {
-    use std::iter::repeat;
+    pub mod render;
+
+    // A step between trees along a sightline, as (rows, columns)
+    pub type Dir = (isize, isize);
+
+    pub const ORTHOGONAL: [Dir; 4] = [(0, 1), (0, -1), (1, 0), (-1, 0)];
+    pub const DIAGONAL: [Dir; 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+    pub const KNIGHT: [Dir; 8] = [(1, 2), (2, 1), (2, -1), (1, -2), (-1, -2), (-2, -1), (-2, 1), (-1, 2)];
 
     pub fn part1(input: &str) -> u64 {
-        let grove: Vec<Vec<u8>> = read_grove(input);
-        let mut visible = vec![vec![false; grove[0].len()]; grove.len()];
-        for r in 0..grove.len() {
-            set_externaly_visible(&grove, &mut visible, repeat(r).zip(0..grove[0].len()));
-            set_externaly_visible(&grove, &mut visible, repeat(r).zip((0..grove[0].len()).rev()));
-        }
-        for c in 0..grove[0].len() {
-            set_externaly_visible(&grove, &mut visible, (0..grove.len()).zip(repeat(c)));
-            set_externaly_visible(&grove, &mut visible, (0..grove.len()).rev().zip(repeat(c)));
-        }
-        visible
+        let grove: Vec<Vec<u32>> = read_grove(input).expect("bad grove");
+        visibility(&grove, &ORTHOGONAL)
             .iter()
             .map(|r| r.iter().filter(|&v| *v).count() as u64)
             .sum()
     }
 
     pub fn part2(input: &str) -> u64 {
-        let grove: Vec<Vec<u8>> = read_grove(input);
-        let mut score = vec![vec![1u64; grove[0].len()]; grove.len()];
-        for r in 0..grove.len() {
-            update_scenic_score(&grove, &mut score, repeat(r).zip(0..grove[0].len()));
-            update_scenic_score(&grove, &mut score, repeat(r).zip((0..grove[0].len()).rev()));
-        }
-        for c in 0..grove[0].len() {
-            update_scenic_score(&grove, &mut score, (0..grove.len()).zip(repeat(c)));
-            update_scenic_score(&grove, &mut score, (0..grove.len()).rev().zip(repeat(c)));
-        }
-        *score
+        let grove: Vec<Vec<u32>> = read_grove(input).expect("bad grove");
+        *scenic_scores(&grove, &ORTHOGONAL)
             .iter()
             .map(|r| r.iter().max().unwrap())
             .max().unwrap()
     }
 
-    fn set_externaly_visible(grove: &Vec<Vec<u8>>, visibility: &mut Vec<Vec<bool>>, indices: impl Iterator<Item = (usize, usize)>) {
-        let mut last_h = -1;
+    // Scenic scores, or which trees are visible, over some sightlines, drawn as a heatmap or a
+    // PGM. A single direction shows how far each tree sees that way.
+    pub fn report(input: &str, args: &[String]) -> Option<Vec<u8>> {
+        let (format, rest) = args.split_first()?;
+        if !["heatmap", "pgm"].contains(&format.as_str()) {
+            return None;
+        }
+        let mut visible = false;
+        let mut dirs = ORTHOGONAL.to_vec();
+        for arg in rest {
+            match arg.as_str() {
+                "scores" => visible = false,
+                "visible" => visible = true,
+                "orthogonal" => dirs = ORTHOGONAL.to_vec(),
+                "diagonal" => dirs = DIAGONAL.to_vec(),
+                "knight" => dirs = KNIGHT.to_vec(),
+                dir => {
+                    let (r, c) = dir.split_once(',')?;
+                    let dir = (r.trim().parse().ok()?, c.trim().parse().ok()?);
+                    if dir == (0, 0) {
+                        return None;
+                    }
+                    dirs = vec![dir];
+                }
+            }
+        }
+        let grove = read_grove(input).expect("bad grove");
+        let grid = match (visible, &dirs[..]) {
+            (true, _) => render::from_bools(&visibility(&grove, &dirs)),
+            (false, &[dir]) => viewing_distances(&grove, dir),
+            (false, _) => scenic_scores(&grove, &dirs),
+        };
+        Some(match format.as_str() {
+            "heatmap" => render::heatmap(&grid).into_bytes(),
+            _ => render::pgm(&grid),
+        })
+    }
+
+    // Whether each tree can be seen from outside the grove, looking back along any of `dirs`
+    pub fn visibility(grove: &[Vec<u32>], dirs: &[Dir]) -> Vec<Vec<bool>> {
+        let mut visible = vec![vec![false; grove[0].len()]; grove.len()];
+        for &dir in dirs {
+            for line in sightlines(grove.len(), grove[0].len(), dir) {
+                set_externaly_visible(grove, &mut visible, line);
+            }
+        }
+        visible
+    }
+
+    // Product of each tree's viewing distances in all of `dirs`
+    pub fn scenic_scores(grove: &[Vec<u32>], dirs: &[Dir]) -> Vec<Vec<u64>> {
+        let mut score = vec![vec![1u64; grove[0].len()]; grove.len()];
+        for &dir in dirs {
+            for line in sightlines(grove.len(), grove[0].len(), dir) {
+                update_scenic_score(grove, &mut score, line);
+            }
+        }
+        score
+    }
+
+    // How many trees each tree sees in a single direction - the factors of its scenic score
+    pub fn viewing_distances(grove: &[Vec<u32>], dir: Dir) -> Vec<Vec<u64>> {
+        scenic_scores(grove, &[dir])
+    }
+
+    // Covers every cell exactly once per direction. Each line starts at the edge `dir` points
+    // to and walks back against it, so a tree's view in `dir` is over the trees already walked.
+    fn sightlines(
+        rows: usize,
+        cols: usize,
+        (dr, dc): Dir,
+    ) -> impl Iterator<Item = impl Iterator<Item = (usize, usize)>> {
+        assert!((dr, dc) != (0, 0));
+        let inside = move |(r, c): (isize, isize)| {
+            r >= 0 && c >= 0 && r < rows as isize && c < cols as isize
+        };
+        (0..rows as isize)
+            .flat_map(move |r| (0..cols as isize).map(move |c| (r, c)))
+            .filter(move |&(r, c)| !inside((r + dr, c + dc)))
+            .map(move |start| {
+                std::iter::successors(Some(start), move |&(r, c)| {
+                    Some((r - dr, c - dc)).filter(|&next| inside(next))
+                })
+                .map(|(r, c)| (r as usize, c as usize))
+            })
+    }
+
+    fn set_externaly_visible(grove: &[Vec<u32>], visibility: &mut [Vec<bool>], indices: impl Iterator<Item = (usize, usize)>) {
+        let mut last_h = None;
         for (r,c) in indices {
-            if grove[r][c] as i8 > last_h {
+            if last_h.is_none_or(|h| grove[r][c] > h) {
                 visibility[r][c] = true;
-                last_h = grove[r][c] as i8;
+                last_h = Some(grove[r][c]);
             }
         }
     }
 
-    fn update_scenic_score(grove: &Vec<Vec<u8>>, score: &mut Vec<Vec<u64>>, indices: impl Iterator<Item = (usize, usize)>) {
-        let indices = indices.collect::<Vec<(usize, usize)>>();
-        for i in 0..indices.len() {
-            let (r,c) = indices[i];
-            let mut dist = 0;
+    // The trees that may still block the view of the next ones, as (height, step) with strictly
+    // decreasing heights: a tree hides every lower (or equally high) tree behind it.
+    fn update_scenic_score(grove: &[Vec<u32>], score: &mut [Vec<u64>], indices: impl Iterator<Item = (usize, usize)>) {
+        let mut blocking: Vec<(u32, u64)> = vec![];
+        for (step, (r, c)) in indices.enumerate() {
+            let step = step as u64;
             let cur_h = grove[r][c];
-            for j in i+1..indices.len() {
-                dist += 1;
-                let (rr,cc) = indices[j];
-                if grove[rr][cc] >=  cur_h {
-                    break;
-                }
+            while blocking.last().is_some_and(|&(h, _)| h < cur_h) {
+                blocking.pop();
             }
+            let dist = match blocking.last() {
+                Some(&(_, blocker_step)) => step - blocker_step,
+                None => step, // sees all the way to the edge
+            };
             score[r][c] *= dist;
+            if blocking.last().is_some_and(|&(h, _)| h == cur_h) {
+                blocking.pop();
+            }
+            blocking.push((cur_h, step));
         }
-        // This has lower complexity, yet it takes longer o.O
-        // let mut height_steps = [0;10];
-        // let mut step = 0;
-        // for (r, c) in indices {
-        //     let cur_h = grove[r][c];
-        //     let mut last_higher_step = 0;
-        //     for i in cur_h..10 {
-        //         last_higher_step = std::cmp::max(last_higher_step, height_steps[i as usize]);
-        //     }
-        //     score[r][c] *= step - last_higher_step;
-        //     height_steps[cur_h as usize] = step;
-        //     step += 1;
-        // }
-    }
-
-    fn read_grove(input: &str) -> Vec<Vec<u8>> {
+    }
+
+    // Lines and columns are 1-based, columns count heights rather than characters
+    #[derive(Debug, PartialEq, Eq)]
+    pub enum GroveError {
+        Empty,
+        BadHeight { line: usize, col: usize },
+        // A row with a different number of trees than the first one
+        Ragged { line: usize },
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Format {
+        // One tree per character, a single digit high
+        Digits,
+        // Whitespace-separated heights, any number of digits each
+        Numbers,
+    }
+
+    // Reads the grove in whichever format it looks like. That's numbers if any line has
+    // whitespace, or if the lines aren't all equally long, which only a single column of numbers
+    // can be. Otherwise it's digits, so a column of numbers that are all as long as each other
+    // ("12\n34") reads as a grid of digits: use `read_grove_as` for those.
+    pub fn read_grove(input: &str) -> Result<Vec<Vec<u32>>, GroveError> {
+        let lines = grove_lines(input);
+        let Some(&(_, first)) = lines.first() else {
+            return Err(GroveError::Empty);
+        };
+        let numbers = lines
+            .iter()
+            .any(|(_, l)| l.contains(char::is_whitespace) || l.len() != first.len());
+        read_grove_as(input, if numbers { Format::Numbers } else { Format::Digits })
+    }
+
+    // The non-blank lines, with their 1-based numbers
+    fn grove_lines(input: &str) -> Vec<(usize, &str)> {
         input
             .lines()
-            .map(|l| l.chars().map(|c| c as u8 - '0' as u8).collect::<Vec<_>>())
-            .collect::<Vec<_>>()
+            .enumerate()
+            .map(|(i, l)| (i + 1, l.trim()))
+            .filter(|(_, l)| !l.is_empty())
+            .collect()
+    }
+
+    pub fn read_grove_as(input: &str, format: Format) -> Result<Vec<Vec<u32>>, GroveError> {
+        let lines = grove_lines(input);
+        if lines.is_empty() {
+            return Err(GroveError::Empty);
+        }
+        let mut grove: Vec<Vec<u32>> = vec![];
+        for &(line, l) in &lines {
+            let bad = |col| GroveError::BadHeight { line, col: col + 1 };
+            let row = match format {
+                Format::Numbers => l
+                    .split_whitespace()
+                    .enumerate()
+                    .map(|(col, h)| h.parse().map_err(|_| bad(col)))
+                    .collect::<Result<Vec<_>, _>>()?,
+                Format::Digits => l
+                    .chars()
+                    .enumerate()
+                    .map(|(col, c)| c.to_digit(10).ok_or(bad(col)))
+                    .collect::<Result<Vec<_>, _>>()?,
+            };
+            if grove.first().is_some_and(|r| r.len() != row.len()) {
+                return Err(GroveError::Ragged { line });
+            }
+            grove.push(row);
+        }
+        Ok(grove)
     }
 }
 
@@ -138,4 +279,214 @@ mod tests {
         assert_eq!(268912, part2(crate::INPUT));
     }
 
+    #[test]
+    fn example_part2() {
+        let input = "\
+30373
+25512
+65332
+33549
+35390";
+        assert_eq!(8, part2(input));
+    }
+
+    #[test]
+    fn whitespace_separated() {
+        let input = "\
+30 0 30 70 30
+20 50 50 10 20
+60 50 30 30 20
+30 30 50 40 90
+30 50 30 90 0";
+        assert_eq!(21, part1(input));
+        assert_eq!(8, part2(input));
+    }
+
+    #[test]
+    fn tall_trees() {
+        let input = "\
+1 1 1 1
+1 1000 999 1
+1 1 1 1";
+        assert_eq!(12, part1(input));
+        assert_eq!(2, part2(input));
+    }
+
+    #[test]
+    fn grove_formats() {
+        // a column of numbers, rather than two rows of digits
+        assert_eq!(Ok(vec![vec![12], vec![3], vec![45]]), read_grove("12\n3\n45"));
+        // equally long numbers in a column look just like digits, unless told otherwise
+        assert_eq!(Ok(vec![vec![1, 2], vec![3, 4]]), read_grove("12\n34\n"));
+        assert_eq!(Ok(vec![vec![12], vec![34]]), read_grove_as("12\n34\n", Format::Numbers));
+        assert_eq!(Err(GroveError::BadHeight { line: 1, col: 2 }), read_grove_as("1 2", Format::Digits));
+        assert_eq!(Err(GroveError::Empty), read_grove_as(" \n", Format::Numbers));
+        assert_eq!(Ok(vec![vec![12, 3], vec![4, 56]]), read_grove("12 3\n4 56"));
+        // one line with whitespace makes them all numbers, so 34 is a single tree
+        assert_eq!(Err(GroveError::Ragged { line: 2 }), read_grove("1 2\n34"));
+
+        assert_eq!(Err(GroveError::Empty), read_grove("\n\n"));
+        assert_eq!(Err(GroveError::BadHeight { line: 2, col: 2 }), read_grove("123\n4x6"));
+        assert_eq!(Err(GroveError::BadHeight { line: 1, col: 3 }), read_grove("1 2 -3"));
+        assert_eq!(Err(GroveError::Ragged { line: 3 }), read_grove("1 2\n3 4\n5"));
+    }
+
+    // Side length n, heights from a fixed LCG so both scorers see the same grove.
+    fn generated_grove(n: usize, max_h: u32) -> String {
+        let mut seed = 12345u64;
+        let mut grove = String::new();
+        for _ in 0..n {
+            for _ in 0..n {
+                seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                grove.push_str(&format!("{} ", (seed >> 33) as u32 % max_h));
+            }
+            grove.push('\n');
+        }
+        grove
+    }
+
+    // Heights drop towards the bottom right corner, so every tree sees far to the right and down:
+    // the worst case for walking each sightline.
+    fn sloped_grove(n: usize) -> String {
+        let mut grove = String::new();
+        for r in 0..n {
+            for c in 0..n {
+                grove.push_str(&format!("{} ", 2 * n - r - c));
+            }
+            grove.push('\n');
+        }
+        grove
+    }
+
+    // The previous quadratic scorer: walk forward from every tree until a blocker.
+    fn naive_part2(input: &str) -> u64 {
+        let grove: Vec<Vec<u32>> = input
+            .lines()
+            .map(|l| l.split_whitespace().map(|h| h.parse().unwrap()).collect())
+            .collect();
+        let (rows, cols) = (grove.len(), grove[0].len());
+        let mut best = 0;
+        for r in 0..rows {
+            for c in 0..cols {
+                let h = grove[r][c];
+                let look = |mut cells: Box<dyn Iterator<Item = u32> + '_>| {
+                    let mut dist = 0;
+                    for other in &mut cells {
+                        dist += 1;
+                        if other >= h {
+                            break;
+                        }
+                    }
+                    dist
+                };
+                let score = look(Box::new((0..c).rev().map(|cc| grove[r][cc])))
+                    * look(Box::new((c + 1..cols).map(|cc| grove[r][cc])))
+                    * look(Box::new((0..r).rev().map(|rr| grove[rr][c])))
+                    * look(Box::new((r + 1..rows).map(|rr| grove[rr][c])));
+                best = best.max(score);
+            }
+        }
+        best
+    }
+
+    #[test]
+    fn diagonal_sightlines() {
+        let grove = read_grove("\
+212
+111
+212").unwrap();
+        let visible = visibility(&grove, &DIAGONAL);
+        assert_eq!(vec![true, true, true], visible[0]);
+        assert_eq!(vec![true, false, true], visible[1]);
+        assert_eq!("@@@\n@ @\n@@@\n", render::heatmap(&render::from_bools(&visible)));
+
+        let scores = scenic_scores(&grove, &DIAGONAL);
+        assert_eq!(vec![0, 0, 0], scores[0]);
+        assert_eq!(vec![0, 1, 0], scores[1]);
+    }
+
+    #[test]
+    fn knight_sightlines() {
+        let grove = read_grove("\
+11111
+11911
+11115").unwrap();
+        // (0,0) -> (1,2) -> (2,4) is a single sightline
+        let forward = viewing_distances(&grove, (1, 2));
+        assert_eq!((1, 1, 0), (forward[0][0], forward[1][2], forward[2][4]));
+        let backward = viewing_distances(&grove, (-1, -2));
+        assert_eq!((0, 1, 1), (backward[0][0], backward[1][2], backward[2][4]));
+
+        let visible = visibility(&grove, &[(1, 2)]);
+        assert!(!visible[0][0]);
+        assert!(visible[1][2]);
+        assert!(visible[2][4]);
+        // every tree in a 3-row grove is a knight's move away from the edge
+        assert!(visibility(&grove, &KNIGHT).iter().flatten().all(|v| *v));
+    }
+
+    #[test]
+    fn example_heatmap() {
+        let grove = read_grove("\
+30373
+25512
+65332
+33549
+35390").unwrap();
+        let scores = scenic_scores(&grove, &ORTHOGONAL);
+        let expected = ["     ", " .=. ", " *.: ", " .@- ", "     "];
+        assert_eq!(expected.join("\n") + "\n", render::heatmap(&scores));
+        let pgm = render::pgm(&scores);
+        assert!(pgm.starts_with(b"P5\n5 5\n255\n"));
+        assert_eq!(255, pgm[pgm.len() - 25 + 17]);
+        assert_eq!(127, pgm[pgm.len() - 25 + 7]);
+
+        let args = |a: &[&str]| a.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let input = "30373\n25512\n65332\n33549\n35390";
+        assert_eq!(None, report(input, &[]));
+        assert_eq!(None, report(input, &args(&["heatmap", "sideways"])));
+        assert_eq!(None, report(input, &args(&["pgm", "0,0"])));
+        let heatmap = report(input, &args(&["heatmap"])).unwrap();
+        assert_eq!(expected.join("\n") + "\n", String::from_utf8(heatmap).unwrap());
+        assert_eq!(Some(pgm), report(input, &args(&["pgm", "scores", "orthogonal"])));
+        let visible = report(input, &args(&["heatmap", "visible"])).unwrap();
+        assert_eq!("@@@@@\n@@@ @\n@@ @@\n@ @ @\n@@@@@\n", String::from_utf8(visible).unwrap());
+        // how far each tree sees to the right
+        let right = report(input, &args(&["heatmap", "0,1"])).unwrap();
+        assert_eq!(render::heatmap(&viewing_distances(&grove, (0, 1))).into_bytes(), right);
+        assert!(report(input, &args(&["pgm", "visible", "knight"])).is_some());
+    }
+
+    #[test]
+    fn generated_part2() {
+        let grove = generated_grove(60, 1000);
+        assert_eq!(naive_part2(&grove), part2(&grove));
+    }
+
+    // On random groves sightlines are short, so walking them is nearly linear too and the gap is
+    // small; sloped groves show the quadratic behaviour.
+    // cargo test --release -- --ignored --nocapture bench_large_grove
+    #[test]
+    #[ignore]
+    fn bench_large_grove() {
+        let groves = [
+            ("1000x1000 digits", generated_grove(1000, 10)),
+            ("1000x1000 heights < 100000", generated_grove(1000, 100_000)),
+            ("1000x1000 sloped", sloped_grove(1000)),
+            ("2000x2000 sloped", sloped_grove(2000)),
+        ];
+        for (name, grove) in groves {
+
+            let start = std::time::Instant::now();
+            let fast = part2(&grove);
+            let fast_dur = start.elapsed().as_micros();
+
+            let start = std::time::Instant::now();
+            let naive = naive_part2(&grove);
+            let naive_dur = start.elapsed().as_micros();
+
+            assert_eq!(naive, fast);
+            println!("{name}: monotonic stack {fast_dur} us, naive {naive_dur} us");
+        }
+    }
 }
@@ -1,4 +1,13 @@
 fn main() {
+    // `puzzle09 visited [knots]` counts the cells every knot of a longer rope visited instead, and
+    // `puzzle09 render [knots] [all]` draws where the rope ends up, or where it is after every step,
+    // and how many cells its tail visited
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(report) = nine::report(INPUT, &args) {
+        print!("{report}");
+        return;
+    }
+
     for f in [nine::part1, nine::part2] {
         let start = std::time::Instant::now();
         let res = f(INPUT);
@@ -22,80 +31,317 @@ const INPUT: &str = include_str!("input.txt");
 mod nine {
     use std::collections::HashSet;
 
+    mod motion;
+    use motion::{parse_motions, Motion};
+
+    mod playback;
+    use playback::Viewport;
+
     pub fn part1(input: &str) -> usize {
-        let mut tracker = RopeTracker::new(1);
-        for l in input.lines() {
-            tracker.next_move(l);
+        let mut tracker = RopeTracker::new(2, &[1]).unwrap();
+        for m in parse_motions(input).unwrap_or_else(|e| panic!("{e}")) {
+            tracker.apply(m);
         }
-        tracker.tail_locs(0).collect::<HashSet<_>>().len()
+        tracker.visited(1).unwrap()
     }
     pub fn part2(input: &str) -> usize {
-        let mut tracker = RopeTracker::new(9);
-        for l in input.lines() {
-            tracker.next_move(l);
+        let mut tracker = RopeTracker::new(10, &[9]).unwrap();
+        for m in parse_motions(input).unwrap_or_else(|e| panic!("{e}")) {
+            tracker.apply(m);
+        }
+        tracker.visited(9).unwrap()
+    }
+
+    // How many cells each knot of a `len` knot rope visited, found in a single pass, or the rope
+    // drawn the way the puzzle draws it
+    pub fn report(input: &str, args: &[String]) -> Option<String> {
+        let (cmd, rest) = args.split_first()?;
+        if cmd != "visited" && cmd != "render" {
+            return None;
+        }
+        let len = rest.first().and_then(|n| n.parse().ok()).unwrap_or(10);
+        let motions = parse_motions(input).unwrap_or_else(|e| panic!("{e}"));
+        let Some(mut tracker) = RopeTracker::tracking_all(len) else {
+            return Some(String::from("a rope needs a head\n"));
+        };
+        if cmd == "render" {
+            let every_step = rest.get(1).is_some_and(|a| a == "all");
+            return Some(render(tracker, motions, every_step));
+        }
+        for m in motions {
+            tracker.apply(m);
         }
-        tracker.tail_locs(8).collect::<HashSet<_>>().len()
+        let counts = tracker.all_visited().into_iter().enumerate();
+        let lines = counts.map(|(k, n)| format!("knot {k}: {}\n", n.unwrap()));
+        Some(lines.collect())
     }
 
+    // Over just enough of the plane to show everywhere the rope goes, with the last knot's trail
+    fn render(tracker: RopeTracker, motions: Vec<Motion>, every_step: bool) -> String {
+        let len = tracker.knots.len();
+        let tail = RopeTracker::new(len, &[len - 1]).unwrap();
+        let viewport = Viewport::fit(tail.playback(motions.clone()).flatten());
+        let mut frames = tracker.playback(motions);
+        let mut out = vec![];
+        if every_step {
+            while frames.next().is_some() {
+                out.push(frames.render(&viewport));
+            }
+        } else {
+            frames.by_ref().for_each(drop);
+            out.push(frames.render(&viewport));
+        }
+        let visited = frames.tracker().visited(len - 1).unwrap();
+        out.push(format!("the tail visited {visited} cells\n"));
+        out.join("\n")
+    }
+
+    // Only the current position of each knot is kept (knot 0 is the head), along with the set of
+    // cells visited so far by each of the tracked knots.
     struct RopeTracker {
-        positions: Vec<Vec<(i32, i32)>>,
+        knots: Vec<(i32, i32)>,
+        visited: Vec<Option<HashSet<(i32, i32)>>>,
     }
 
     impl RopeTracker {
-        fn new(tail_len: usize) -> Self {
-            Self {
-                positions: vec![vec![(0, 0)]; tail_len + 1],
+        // None without a head, or if a tracked knot is past the end of the rope
+        fn new(len: usize, tracked: &[usize]) -> Option<Self> {
+            if len == 0 || tracked.iter().any(|&k| k >= len) {
+                return None;
             }
+            let mut visited = vec![None; len];
+            for &k in tracked {
+                visited[k] = Some(HashSet::from([(0, 0)]));
+            }
+            Some(Self {
+                knots: vec![(0, 0); len],
+                visited,
+            })
         }
 
-        fn tail_locs(&self, tail_idx: usize) -> impl Iterator<Item = (i32, i32)> {
-            self.positions[tail_idx + 1].clone().into_iter()
+        fn tracking_all(len: usize) -> Option<Self> {
+            Self::new(len, &(0..len).collect::<Vec<_>>())
         }
 
-        fn next_move(&mut self, m: &str) {
-            let mut parts = m.split_whitespace();
-            let (dir, amount) = (
-                parts.next().unwrap(),
-                parts.next().unwrap().parse::<i32>().unwrap(),
-            );
-            for _ in 0..amount {
-                self.move_h(dir);
+        // Number of distinct cells knot `k` has been in, if it's tracked
+        fn visited(&self, k: usize) -> Option<usize> {
+            self.visited[k].as_ref().map(HashSet::len)
+        }
+
+        fn all_visited(&self) -> Vec<Option<usize>> {
+            (0..self.knots.len()).map(|k| self.visited(k)).collect()
+        }
+
+        fn apply(&mut self, m: Motion) {
+            for step in m.steps() {
+                self.step_head(step);
             }
         }
 
-        fn move_h(&mut self, dir: &str) {
-            let mut next_h = self.positions[0].last().unwrap().clone();
-            match dir {
-                "R" => next_h.0 += 1,
-                "L" => next_h.0 -= 1,
-                "U" => next_h.1 += 1,
-                "D" => next_h.1 -= 1,
-                _ => unreachable!(),
-            };
-            self.positions[0].push(next_h);
-            for i in 1..self.positions.len() {
-                self.snap_tail(i);
+        fn step_head(&mut self, (dx, dy): (i32, i32)) {
+            self.knots[0].0 += dx;
+            self.knots[0].1 += dy;
+            self.record(0);
+            for i in 1..self.knots.len() {
+                if !self.snap_tail(i) {
+                    break; // the rest of the rope stays put as well
+                }
             }
         }
 
-        fn snap_tail(&mut self, idx: usize) {
-            let prev_pos = self.positions[idx - 1].last().unwrap();
-            let cur_pos = self.positions[idx].last().unwrap().clone();
+        // Returns whether the knot moved
+        fn snap_tail(&mut self, idx: usize) -> bool {
+            let prev_pos = self.knots[idx - 1];
+            let cur_pos = self.knots[idx];
             let dx = prev_pos.0 - cur_pos.0;
             let dy = prev_pos.1 - cur_pos.1;
             if dx.abs() > 1 || dy.abs() > 1 {
                 // needs snapping
-                let normalized = |n: i32| n.checked_div(n.abs()).unwrap_or_default();
-                self.positions[idx].push((cur_pos.0 + normalized(dx), cur_pos.1 + normalized(dy)))
+                self.knots[idx] = (cur_pos.0 + dx.signum(), cur_pos.1 + dy.signum());
+                self.record(idx);
+                true
+            } else {
+                false
+            }
+        }
+
+        fn record(&mut self, idx: usize) {
+            if let Some(visited) = &mut self.visited[idx] {
+                visited.insert(self.knots[idx]);
             }
         }
     }
 
     #[cfg(test)]
     mod tests {
+        use super::motion::{parse_motions, Motion, MotionError};
+        use super::playback::Viewport;
+        use super::RopeTracker;
+
+        const EXAMPLE: &str = "\
+R 4
+U 4
+L 3
+D 1
+R 4
+D 1
+L 5
+R 2";
+
+        const LARGER_EXAMPLE: &str = "\
+R 5
+U 8
+L 8
+D 3
+R 17
+D 10
+L 25
+U 20";
+
+        #[test]
+        fn example_part1() {
+            assert_eq!(13, super::part1(EXAMPLE));
+        }
+
         #[test]
         fn real_part1() {
             assert_eq!(5735, super::part1(crate::INPUT));
         }
+
+        #[test]
+        fn example_part2() {
+            assert_eq!(1, super::part2(EXAMPLE));
+            assert_eq!(36, super::part2(LARGER_EXAMPLE));
+        }
+
+        #[test]
+        fn real_part2() {
+            assert_eq!(2478, super::part2(crate::INPUT));
+        }
+
+        #[test]
+        fn all_knots_in_one_pass() {
+            let mut tracker = RopeTracker::tracking_all(10).unwrap();
+            for m in parse_motions(EXAMPLE).unwrap() {
+                tracker.apply(m);
+            }
+            assert_eq!(Some(13), tracker.visited(1));
+            assert_eq!(Some(1), tracker.visited(9));
+            let all = tracker.all_visited();
+            assert_eq!(10, all.len());
+            assert!(all.windows(2).all(|w| w[0] >= w[1]));
+
+            let untracked = RopeTracker::new(10, &[9]).unwrap();
+            assert_eq!(None, untracked.visited(1));
+            assert!(RopeTracker::new(10, &[1, 10]).is_none());
+            assert!(RopeTracker::tracking_all(0).is_none());
+
+            let args = |a: &[&str]| a.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+            assert_eq!(None, super::report(EXAMPLE, &[]));
+            assert_eq!(
+                Some(String::from("knot 0: 21\nknot 1: 13\n")),
+                super::report(EXAMPLE, &args(&["visited", "2"]))
+            );
+            let report = super::report(EXAMPLE, &args(&["visited"])).unwrap();
+            assert_eq!(Some("knot 9: 1"), report.lines().last());
+        }
+
+        #[test]
+        fn extended_motions() {
+            let motions = parse_motions(
+                "\
+# diagonals
+UR 2
+DL 1 # back a bit
+
+M 3,-1
+M -2,4",
+            )
+            .unwrap();
+            assert_eq!(
+                vec![
+                    Motion::Steps((1, 1), 2),
+                    Motion::Steps((-1, -1), 1),
+                    Motion::By(3, -1),
+                    Motion::By(-2, 4),
+                ],
+                motions
+            );
+            assert_eq!(
+                vec![(1, -1), (1, 0), (1, 0)],
+                motions[2].steps().collect::<Vec<_>>()
+            );
+            assert_eq!(
+                vec![(-1, 1), (-1, 1), (0, 1), (0, 1)],
+                motions[3].steps().collect::<Vec<_>>()
+            );
+
+            let mut tracker = RopeTracker::tracking_all(2).unwrap();
+            for m in motions {
+                tracker.apply(m);
+            }
+            assert_eq!((2, 4), tracker.knots[0]);
+            assert_eq!((2, 3), tracker.knots[1]);
+        }
+
+        #[test]
+        fn motion_errors() {
+            assert_eq!(
+                Err(MotionError::UnknownDirection { line: 2, dir: String::from("X") }),
+                parse_motions("R 1\nX 2")
+            );
+            assert_eq!(
+                Err(MotionError::BadCount { line: 1, count: String::from("-3") }),
+                parse_motions("R -3")
+            );
+            assert_eq!(
+                Err(MotionError::BadOffset { line: 1, offset: String::from("3") }),
+                parse_motions("M 3")
+            );
+            assert_eq!(
+                Err(MotionError::MissingArgument { line: 3 }),
+                parse_motions("# only\n\nU")
+            );
+        }
+
+        #[test]
+        fn motion_error_messages() {
+            let message = |input| parse_motions(input).unwrap_err().to_string();
+            assert_eq!("line 2: unknown direction X", message("R 1\nX 2"));
+            assert_eq!("line 1: -3 isn't a number of steps", message("R -3"));
+            assert_eq!("line 1: 3 isn't an offset like 3,-2", message("M 3"));
+            assert_eq!("line 1: the motion needs a count or an offset", message("U"));
+        }
+
+        #[test]
+        fn playback() {
+            let viewport = Viewport { left: 0, bottom: 0, width: 6, height: 5 };
+
+            let mut frames = RopeTracker::new(10, &[9]).unwrap().playback(parse_motions("R 4").unwrap());
+            let last = frames.by_ref().last().unwrap();
+            assert_eq!((4, 0), last[0]);
+            assert_eq!(
+                "......\n......\n......\n......\n4321H.\n",
+                frames.render(&viewport)
+            );
+
+            let mut frames = RopeTracker::new(2, &[1]).unwrap().playback(parse_motions(EXAMPLE).unwrap());
+            assert_eq!(24, frames.by_ref().count());
+            assert_eq!(Some(13), frames.tracker().visited(1));
+            let expected = ["..##..", "...##.", ".1H##.", "....#.", "s###.."];
+            assert_eq!(expected.join("\n") + "\n", frames.render(&viewport));
+
+            let cells = [(3, -1), (-2, 4)];
+            let fitted = Viewport::fit(cells);
+            assert_eq!((-2, -1, 6, 6), (fitted.left, fitted.bottom, fitted.width, fitted.height));
+
+            let args = |a: &[&str]| a.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+            let last = super::report(EXAMPLE, &args(&["render", "2"])).unwrap();
+            let footer = "the tail visited 13 cells\n";
+            assert_eq!(expected.join("\n") + "\n\n" + footer, last);
+            let every = super::report(EXAMPLE, &args(&["render", "2", "all"])).unwrap();
+            assert_eq!(25, every.split("\n\n").count());
+            assert!(every.ends_with(&last));
+        }
     }
 }
@@ -0,0 +1,113 @@
+// One motion per line:
+//   R 4, UL 2, ...  - move the head in one of the 8 directions, step by step
+//   M 3,-2          - move the head by an offset (x,y), diagonally first
+//   # ...           - comment, either a whole line or after a motion. Blank lines are skipped.
+
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Motion {
+    Steps((i32, i32), u32),
+    By(i32, i32),
+}
+
+// Line numbers start from 1
+#[derive(Debug, PartialEq, Eq)]
+pub enum MotionError {
+    UnknownDirection { line: usize, dir: String },
+    BadCount { line: usize, count: String },
+    BadOffset { line: usize, offset: String },
+    MissingArgument { line: usize },
+}
+
+impl fmt::Display for MotionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MotionError::UnknownDirection { line, dir } => {
+                write!(f, "line {line}: unknown direction {dir}")
+            }
+            MotionError::BadCount { line, count } => {
+                write!(f, "line {line}: {count} isn't a number of steps")
+            }
+            MotionError::BadOffset { line, offset } => {
+                write!(f, "line {line}: {offset} isn't an offset like 3,-2")
+            }
+            MotionError::MissingArgument { line } => {
+                write!(f, "line {line}: the motion needs a count or an offset")
+            }
+        }
+    }
+}
+
+impl Motion {
+    // The unit steps (each coordinate is -1, 0 or 1) the head takes
+    pub fn steps(self) -> impl Iterator<Item = (i32, i32)> {
+        let (diagonal, straight) = match self {
+            Motion::Steps(dir, count) => ((dir, count), ((0, 0), 0)),
+            Motion::By(dx, dy) => {
+                let diagonal = dx.abs().min(dy.abs()) as u32;
+                let straight = dx.abs().max(dy.abs()) as u32 - diagonal;
+                let straight_dir = if dx.abs() > dy.abs() {
+                    (dx.signum(), 0)
+                } else {
+                    (0, dy.signum())
+                };
+                (((dx.signum(), dy.signum()), diagonal), (straight_dir, straight))
+            }
+        };
+        std::iter::repeat_n(diagonal.0, diagonal.1 as usize)
+            .chain(std::iter::repeat_n(straight.0, straight.1 as usize))
+    }
+}
+
+pub fn parse_motions(input: &str) -> Result<Vec<Motion>, MotionError> {
+    let mut motions = vec![];
+    for (i, l) in input.lines().enumerate() {
+        if let Some(m) = parse_line(i + 1, l)? {
+            motions.push(m);
+        }
+    }
+    Ok(motions)
+}
+
+fn parse_line(line: usize, l: &str) -> Result<Option<Motion>, MotionError> {
+    let l = l.split('#').next().unwrap();
+    let mut parts = l.split_whitespace();
+    let Some(dir) = parts.next() else {
+        return Ok(None);
+    };
+    let arg = parts.next().ok_or(MotionError::MissingArgument { line })?;
+    if dir == "M" {
+        let offset = arg
+            .split_once(',')
+            .and_then(|(x, y)| Some((x.parse().ok()?, y.parse().ok()?)));
+        return match offset {
+            Some((dx, dy)) => Ok(Some(Motion::By(dx, dy))),
+            None => Err(MotionError::BadOffset {
+                line,
+                offset: String::from(arg),
+            }),
+        };
+    }
+    let dir = match dir {
+        "R" => (1, 0),
+        "L" => (-1, 0),
+        "U" => (0, 1),
+        "D" => (0, -1),
+        "UR" => (1, 1),
+        "UL" => (-1, 1),
+        "DR" => (1, -1),
+        "DL" => (-1, -1),
+        _ => {
+            return Err(MotionError::UnknownDirection {
+                line,
+                dir: String::from(dir),
+            })
+        }
+    };
+    let count = arg.parse::<u32>().map_err(|_| MotionError::BadCount {
+        line,
+        count: String::from(arg),
+    })?;
+    Ok(Some(Motion::Steps(dir, count)))
+}
@@ -0,0 +1,88 @@
+use super::motion::Motion;
+use super::RopeTracker;
+
+// The part of the plane to draw; (left, bottom) is the lower left corner
+pub struct Viewport {
+    pub left: i32,
+    pub bottom: i32,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Viewport {
+    // The smallest one showing the start and all of `cells`
+    pub fn fit(cells: impl IntoIterator<Item = (i32, i32)>) -> Self {
+        let (mut min, mut max) = ((0, 0), (0, 0));
+        for (x, y) in cells {
+            min = (min.0.min(x), min.1.min(y));
+            max = (max.0.max(x), max.1.max(y));
+        }
+        Viewport {
+            left: min.0,
+            bottom: min.1,
+            width: (max.0 - min.0) as usize + 1,
+            height: (max.1 - min.1) as usize + 1,
+        }
+    }
+}
+
+// Runs the motions one head step at a time, yielding the whole rope (head first) after each step
+pub struct Playback<I> {
+    tracker: RopeTracker,
+    steps: I,
+}
+
+impl RopeTracker {
+    pub fn playback(
+        self,
+        motions: impl IntoIterator<Item = Motion>,
+    ) -> Playback<impl Iterator<Item = (i32, i32)>> {
+        Playback {
+            tracker: self,
+            steps: motions.into_iter().flat_map(Motion::steps),
+        }
+    }
+
+    // Same markings as the puzzle: H for the head, knot numbers, s for the start and # for the
+    // trail of the last tracked knot. Knots closer to the head are drawn on top.
+    pub fn render(&self, vp: &Viewport) -> String {
+        let trail = self.visited.iter().rev().flatten().next();
+        let mut out = String::new();
+        for row in (0..vp.height).rev() {
+            let y = vp.bottom + row as i32;
+            for col in 0..vp.width {
+                let x = vp.left + col as i32;
+                let knot = self.knots.iter().position(|&k| k == (x, y));
+                out.push(match knot {
+                    Some(0) => 'H',
+                    Some(k) => char::from_digit(k as u32 % 36, 36).unwrap(),
+                    None if (x, y) == (0, 0) => 's',
+                    None if trail.is_some_and(|t| t.contains(&(x, y))) => '#',
+                    None => '.',
+                });
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+impl<I: Iterator<Item = (i32, i32)>> Playback<I> {
+    pub fn tracker(&self) -> &RopeTracker {
+        &self.tracker
+    }
+
+    pub fn render(&self, vp: &Viewport) -> String {
+        self.tracker.render(vp)
+    }
+}
+
+impl<I: Iterator<Item = (i32, i32)>> Iterator for Playback<I> {
+    type Item = Vec<(i32, i32)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let step = self.steps.next()?;
+        self.tracker.step_head(step);
+        Some(self.tracker.knots.clone())
+    }
+}
@@ -0,0 +1,254 @@
+use std::collections::HashMap;
+use std::ops::Index;
+
+// An instruction's effect is applied at the end of its last cycle
+#[derive(Clone, Copy)]
+pub struct Opcode {
+    pub name: &'static str,
+    pub cycles: usize,
+    pub nargs: usize,
+    pub exec: fn(&mut Registers, &[i64]),
+}
+
+// The opcodes a program may use, and the registers (with their initial values) they work on
+#[derive(Clone)]
+pub struct InstructionSet {
+    ops: Vec<Opcode>,
+    registers: Vec<(&'static str, i64)>,
+}
+
+impl InstructionSet {
+    pub fn standard() -> Self {
+        Self {
+            ops: vec![],
+            registers: vec![],
+        }
+        .with_register("x", 1)
+        .with_opcode(Opcode {
+            name: "noop",
+            cycles: 1,
+            nargs: 0,
+            exec: |_, _| (),
+        })
+        .with_opcode(Opcode {
+            name: "addx",
+            cycles: 2,
+            nargs: 1,
+            exec: |regs, args| regs.set("x", regs["x"] + args[0]),
+        })
+    }
+
+    pub fn with_opcode(mut self, op: Opcode) -> Self {
+        assert!(op.cycles > 0, "{} must take at least a cycle", op.name);
+        self.ops.retain(|o| o.name != op.name);
+        self.ops.push(op);
+        self
+    }
+
+    pub fn with_register(mut self, name: &'static str, init: i64) -> Self {
+        self.registers.retain(|(n, _)| *n != name);
+        self.registers.push((name, init));
+        self
+    }
+
+    pub fn parse(&self, program: &str) -> Result<Vec<Instr>, CpuError> {
+        let mut instrs = vec![];
+        for (i, l) in program.lines().enumerate() {
+            let line = i + 1;
+            let mut parts = l.split_whitespace();
+            let Some(name) = parts.next() else {
+                continue;
+            };
+            let op = self
+                .ops
+                .iter()
+                .position(|o| o.name == name)
+                .ok_or_else(|| CpuError::UnknownOpcode {
+                    line,
+                    name: String::from(name),
+                })?;
+            let args = parts
+                .map(|a| {
+                    a.parse::<i64>().map_err(|_| CpuError::BadOperand {
+                        line,
+                        operand: String::from(a),
+                    })
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            if args.len() != self.ops[op].nargs {
+                return Err(CpuError::WrongArity {
+                    line,
+                    expected: self.ops[op].nargs,
+                    found: args.len(),
+                });
+            }
+            instrs.push(Instr { op, args });
+        }
+        Ok(instrs)
+    }
+}
+
+// Line numbers start from 1
+#[derive(Debug, PartialEq, Eq)]
+pub enum CpuError {
+    UnknownOpcode { line: usize, name: String },
+    BadOperand { line: usize, operand: String },
+    WrongArity { line: usize, expected: usize, found: usize },
+}
+
+// An opcode (index into the instruction set it was parsed with) and its operands
+#[derive(Debug, Clone)]
+pub struct Instr {
+    op: usize,
+    args: Vec<i64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Registers {
+    vals: HashMap<&'static str, i64>,
+}
+
+impl Registers {
+    pub fn get(&self, name: &str) -> Option<i64> {
+        self.vals.get(name).copied()
+    }
+
+    pub fn set(&mut self, name: &str, val: i64) {
+        *self
+            .vals
+            .get_mut(name)
+            .unwrap_or_else(|| panic!("no register {name}")) = val;
+    }
+}
+
+impl Index<&str> for Registers {
+    type Output = i64;
+
+    fn index(&self, name: &str) -> &i64 {
+        &self.vals[name]
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Breakpoint {
+    Cycle(usize), // before the given cycle (counted from 1) starts
+    Pc(usize),    // before the instruction at the given index starts
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Stop {
+    Halted,
+    Break(Breakpoint),
+}
+
+type Hook<'h> = Box<dyn FnMut(usize, &Registers) + 'h>;
+
+// Hooks are called during every cycle, with the cycle's number (from 1) and the registers as
+// they are during it - before the effect of an instruction ending in this cycle.
+pub struct Cpu<'h> {
+    isa: InstructionSet,
+    program: Vec<Instr>,
+    regs: Registers,
+    pc: usize,
+    cycle: usize,     // completed cycles
+    remaining: usize, // cycles left for the instruction at pc, 0 if it hasn't started
+    hooks: Vec<Hook<'h>>,
+    breakpoints: Vec<Breakpoint>,
+    stopped_at: Option<usize>,
+}
+
+impl<'h> Cpu<'h> {
+    pub fn new(isa: InstructionSet, program: Vec<Instr>) -> Self {
+        let regs = Registers {
+            vals: isa.registers.iter().copied().collect(),
+        };
+        Self {
+            isa,
+            program,
+            regs,
+            pc: 0,
+            cycle: 0,
+            remaining: 0,
+            hooks: vec![],
+            breakpoints: vec![],
+            stopped_at: None,
+        }
+    }
+
+    pub fn add_hook(&mut self, hook: impl FnMut(usize, &Registers) + 'h) {
+        self.hooks.push(Box::new(hook));
+    }
+
+    pub fn add_breakpoint(&mut self, bp: Breakpoint) {
+        self.breakpoints.push(bp);
+    }
+
+    pub fn regs(&self) -> &Registers {
+        &self.regs
+    }
+
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn cycle(&self) -> usize {
+        self.cycle
+    }
+
+    pub fn halted(&self) -> bool {
+        self.pc >= self.program.len()
+    }
+
+    // Runs a single cycle, returning its number, or None if the program is over
+    pub fn step(&mut self) -> Option<usize> {
+        let instr = self.program.get(self.pc)?;
+        let op = self.isa.ops[instr.op];
+        if self.remaining == 0 {
+            self.remaining = op.cycles;
+        }
+        self.cycle += 1;
+        for hook in &mut self.hooks {
+            hook(self.cycle, &self.regs);
+        }
+        self.remaining -= 1;
+        if self.remaining == 0 {
+            (op.exec)(&mut self.regs, &instr.args);
+            self.pc += 1;
+        }
+        Some(self.cycle)
+    }
+
+    // Runs the rest of the current instruction (or all of the next one)
+    pub fn step_instruction(&mut self) -> Option<usize> {
+        let pc = self.pc;
+        while self.pc == pc {
+            self.step()?;
+        }
+        Some(self.cycle)
+    }
+
+    // Runs until the program is over or a breakpoint is reached. Running again resumes past it.
+    pub fn run(&mut self) -> Stop {
+        loop {
+            if self.stopped_at != Some(self.cycle) {
+                if let Some(bp) = self.breakpoint_hit() {
+                    self.stopped_at = Some(self.cycle);
+                    return Stop::Break(bp);
+                }
+            }
+            if self.step().is_none() {
+                return Stop::Halted;
+            }
+        }
+    }
+
+    fn breakpoint_hit(&self) -> Option<Breakpoint> {
+        if self.halted() {
+            return None;
+        }
+        self.breakpoints.iter().copied().find(|bp| match bp {
+            Breakpoint::Cycle(c) => *c == self.cycle + 1,
+            Breakpoint::Pc(pc) => *pc == self.pc && self.remaining == 0,
+        })
+    }
+}
@@ -0,0 +1,133 @@
+use std::fmt;
+
+// Glyphs are 4x6 pixels, one blank column apart
+const GLYPH_W: usize = 4;
+const GLYPH_H: usize = 6;
+const FONT: [(char, &str); 18] = [
+    ('A', ".##.#..##..######..##..#"),
+    ('B', "###.#..####.#..##..####."),
+    ('C', ".##.#..##...#...#..#.##."),
+    ('E', "#####...###.#...#...####"),
+    ('F', "#####...###.#...#...#..."),
+    ('G', ".##.#..##...#.###..#.###"),
+    ('H', "#..##..######..##..##..#"),
+    ('I', ".###..#...#...#...#..###"),
+    ('J', "..##...#...#...##..#.##."),
+    ('K', "#..##.#.##..#.#.#.#.#..#"),
+    ('L', "#...#...#...#...#...####"),
+    ('O', ".##.#..##..##..##..#.##."),
+    ('P', "###.#..##..####.#...#..."),
+    ('R', "###.#..##..####.#.#.#..#"),
+    ('S', ".####...#....##....####."),
+    ('U', "#..##..##..##..##..#.##."),
+    ('Z', "####...#..#..#..#...####"),
+    (' ', "........................"),
+];
+
+// Each cycle the beam draws one pixel, left to right and top to bottom, lit if the 3 pixels
+// wide sprite covers it.
+pub struct Crt {
+    width: usize,
+    height: usize,
+    pixels: Vec<bool>,
+}
+
+// Both carry the whole rendered framebuffer
+#[derive(Debug, PartialEq, Eq)]
+pub enum OcrError {
+    // Fewer rows than a glyph has
+    TooShort { height: usize, bitmap: String },
+    Unrecognised {
+        position: usize, // glyph index from the left
+        bitmap: String,
+    },
+}
+
+impl fmt::Display for OcrError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OcrError::TooShort { height, bitmap } => write!(
+                f,
+                "the screen is {height} rows high, letters need {GLYPH_H}:\n{bitmap}"
+            ),
+            // with the glyph marked under the screen
+            OcrError::Unrecognised { position, bitmap } => write!(
+                f,
+                "unrecognised glyph #{position}:\n{bitmap}{}{}\n",
+                " ".repeat(position * (GLYPH_W + 1)),
+                "^".repeat(GLYPH_W)
+            ),
+        }
+    }
+}
+
+impl Crt {
+    // None for a screen without any pixels
+    pub fn new(width: usize, height: usize) -> Option<Self> {
+        if width == 0 || height == 0 {
+            return None;
+        }
+        Some(Self {
+            width,
+            height,
+            pixels: vec![false; width * height],
+        })
+    }
+
+    // `cycle` counts from 1, cycles past the last pixel are ignored
+    pub fn draw(&mut self, cycle: usize, sprite_x: i64) {
+        let pos = cycle - 1;
+        if pos < self.pixels.len() {
+            let col = (pos % self.width) as i64;
+            self.pixels[pos] = (sprite_x - 1..=sprite_x + 1).contains(&col);
+        }
+    }
+
+    pub fn lit(&self, x: usize, y: usize) -> bool {
+        self.pixels[y * self.width + x]
+    }
+
+    // Reads the top GLYPH_H rows as a line of text
+    pub fn read(&self) -> Result<String, OcrError> {
+        if self.height < GLYPH_H {
+            return Err(OcrError::TooShort {
+                height: self.height,
+                bitmap: self.to_string(),
+            });
+        }
+        (0..(self.width + 1) / (GLYPH_W + 1))
+            .map(|i| {
+                let bitmap = self.glyph(i * (GLYPH_W + 1));
+                FONT.iter()
+                    .find(|(_, g)| **g == bitmap)
+                    .map(|(c, _)| *c)
+                    .ok_or_else(|| OcrError::Unrecognised {
+                        position: i,
+                        bitmap: self.to_string(),
+                    })
+            })
+            .collect()
+    }
+
+    fn glyph(&self, left: usize) -> String {
+        let mut bitmap = String::new();
+        for y in 0..GLYPH_H {
+            for x in left..left + GLYPH_W {
+                bitmap.push(if self.lit(x, y) { '#' } else { '.' });
+            }
+        }
+        bitmap
+    }
+}
+
+impl fmt::Display for Crt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for row in self.pixels.chunks(self.width) {
+            for &p in row {
+                write!(f, "{}", if p { '#' } else { '.' })?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
@@ -1,91 +1,262 @@
 fn main() {
-    for func in [day10::part1, day10::part2] {
-        let start = std::time::Instant::now();
-        let res = func(INPUT);
-        let dur = start.elapsed().as_nanos();
-
-        println!("{res} [{dur} ns]");
+    // `puzzle10 trace [cycle=N|pc=N]...` shows the CPU after each instruction instead, or at each
+    // of the breakpoints if there are any
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(trace) = day10::report(INPUT, &args) {
+        print!("{trace}");
+        return;
     }
+
+    timed(|| day10::part1(INPUT));
+    timed(|| day10::part2(INPUT));
+}
+
+fn timed<T: std::fmt::Display>(func: impl Fn() -> T) {
+    let start = std::time::Instant::now();
+    let res = func();
+    let dur = start.elapsed().as_nanos();
+
+    println!("{res} [{dur} ns]");
 }
 
 mod day10 {
+    mod cpu;
+    use cpu::{Breakpoint, Cpu, InstructionSet, Stop};
+
+    mod crt;
+    use crt::Crt;
+
     pub fn part1(input: &str) -> u64 {
-        let cycle_vals = val_per_cycle(input);
+        let isa = InstructionSet::standard();
+        let program = isa.parse(input).unwrap();
         let mut total = 0;
-        for i in (19..=219).step_by(40) {
-            // println!("{}", cycle_vals[i]);
-            total += (cycle_vals[i] * (i as i32 + 1)) as u64
-        }
-        total
+        let mut cpu = Cpu::new(isa, program);
+        cpu.add_hook(|cycle, regs| {
+            if cycle <= 220 && cycle % 40 == 20 {
+                total += cycle as i64 * regs["x"];
+            }
+        });
+        cpu.run();
+        drop(cpu);
+        total as u64
     }
 
-    pub fn part2(input: &str) -> u64 {
-        let cycle_vals = val_per_cycle(input);
-        let mut crt = vec![];
-        for cycle in 0..240 {
-            let x = cycle_vals[cycle];
-            if [x-1, x, x+1].contains(&((cycle % 40) as i32)) {
-                crt.push('#');
-            } else {
-                crt.push(' ');
-            }
-        }
-        let mut it = crt.iter();
-        for _ in 0..6 {
-            for _ in 0..8 {
-                for _ in 0..5 {
-                    print!("{}", it.next().unwrap())
-                }
-                print!(" ");
-            }
-            println!();
-        }
-        0
+    pub fn part2(input: &str) -> String {
+        let crt = render(input, 40, 6).unwrap();
+        crt.read().unwrap_or_else(|e| panic!("{e}"))
     }
 
-    fn val_per_cycle(input: &str) -> Vec<i32> {
-        let mut cycle_vals = vec![];
-        let mut val = 1;
-        for l in input.lines() {
-            if let Cmd::Addx(num) = Cmd::from(l) {
-                // addx num
-                cycle_vals.push(val);
-                cycle_vals.push(val);
-                val += num;
-            } else {
-                // noop
-                cycle_vals.push(val);
+    pub fn report(input: &str, args: &[String]) -> Option<String> {
+        let (cmd, rest) = args.split_first()?;
+        if cmd != "trace" {
+            return None;
+        }
+        let breakpoints = rest
+            .iter()
+            .map(|a| match a.split_once('=')? {
+                ("cycle", n) => Some(Breakpoint::Cycle(n.parse().ok()?)),
+                ("pc", n) => Some(Breakpoint::Pc(n.parse().ok()?)),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()?;
+        let isa = InstructionSet::standard();
+        let program = isa.parse(input).unwrap();
+        let mut cpu = Cpu::new(isa, program);
+        let state = |cpu: &Cpu| {
+            let x = cpu.regs().get("x").unwrap();
+            format!("cycle {}, pc {}, x = {x}\n", cpu.cycle(), cpu.pc())
+        };
+        let mut out = String::new();
+        if breakpoints.is_empty() {
+            while cpu.step_instruction().is_some() {
+                out += &state(&cpu);
+            }
+        } else {
+            for bp in breakpoints {
+                cpu.add_breakpoint(bp);
+            }
+            while let Stop::Break(bp) = cpu.run() {
+                out += &format!("{bp:?}: {}", state(&cpu));
             }
+            out += &format!("Halted: {}", state(&cpu));
         }
-        cycle_vals
+        Some(out)
     }
 
-    enum Cmd {
-        Nop,
-        Addx(i32),
-    }
-
-    impl From<&str> for Cmd {
-        fn from(s: &str) -> Self {
-            let re = regex::Regex::new(r"(noop|addx\s(-?\d+))").unwrap();
-            let caps = re.captures(s).unwrap();
-            assert!(caps.get(1).is_some());
-            if let Some(num_str) = caps.get(2) {
-                Cmd::Addx(num_str.as_str().parse().unwrap())
-            } else {
-                Cmd::Nop
-            }
-        }
+    // None if the screen has no pixels
+    fn render(input: &str, width: usize, height: usize) -> Option<Crt> {
+        let isa = InstructionSet::standard();
+        let program = isa.parse(input).unwrap();
+        let mut crt = Crt::new(width, height)?;
+        let mut cpu = Cpu::new(isa, program);
+        cpu.add_hook(|cycle, regs| crt.draw(cycle, regs["x"]));
+        cpu.run();
+        drop(cpu);
+        Some(crt)
     }
 
     #[cfg(test)]
     mod tests {
-        use super::part1;
+        use super::cpu::{Breakpoint, Cpu, CpuError, InstructionSet, Opcode, Stop};
+        use super::crt::OcrError;
+        use super::{part1, part2, render};
+
+        const EXAMPLE: &str = include_str!("example.txt");
+
+        #[test]
+        fn example_part1() {
+            assert_eq!(13140, part1(EXAMPLE));
+        }
 
         #[test]
         fn real_part1() {
             assert_eq!(12640, part1(crate::INPUT));
         }
+
+        #[test]
+        fn example_part2() {
+            let expected = "\
+##..##..##..##..##..##..##..##..##..##..
+###...###...###...###...###...###...###.
+####....####....####....####....####....
+#####.....#####.....#####.....#####.....
+######......######......######......####
+#######.......#######.......#######.....
+";
+            let crt = render(EXAMPLE, 40, 6).unwrap();
+            assert_eq!(expected, crt.to_string());
+            let err = crt.read().unwrap_err();
+            assert_eq!(
+                OcrError::Unrecognised {
+                    position: 0,
+                    bitmap: String::from(expected),
+                },
+                err
+            );
+            assert!(err.to_string().ends_with("\n^^^^\n"));
+        }
+
+        #[test]
+        fn real_part2() {
+            assert_eq!("EHBZLRJR", part2(crate::INPUT));
+        }
+
+        #[test]
+        fn framebuffer_size() {
+            let crt = render(EXAMPLE, 10, 3).unwrap();
+            assert_eq!("##..##..##\n....##....\n..........\n", crt.to_string());
+            assert!(crt.lit(0, 0));
+            assert!(!crt.lit(3, 1));
+            assert_eq!(
+                Err(OcrError::TooShort {
+                    height: 3,
+                    bitmap: crt.to_string()
+                }),
+                crt.read()
+            );
+            assert!(render(EXAMPLE, 0, 6).is_none());
+            assert!(render(EXAMPLE, 40, 0).is_none());
+        }
+
+        #[test]
+        fn small_program() {
+            let isa = InstructionSet::standard();
+            let program = isa.parse("noop\naddx 3\naddx -5").unwrap();
+            let mut during = vec![];
+            let mut cpu = Cpu::new(isa, program);
+            cpu.add_hook(|_, regs| during.push(regs["x"]));
+            assert_eq!(Stop::Halted, cpu.run());
+            assert_eq!(5, cpu.cycle());
+            assert_eq!(-1, cpu.regs()["x"]);
+            drop(cpu);
+            assert_eq!(vec![1, 1, 1, 4, 4], during);
+        }
+
+        #[test]
+        fn parse_errors() {
+            let isa = InstructionSet::standard();
+            assert_eq!(
+                Err(CpuError::UnknownOpcode { line: 2, name: String::from("mulx") }),
+                isa.parse("noop\nmulx 2").map(|_| ())
+            );
+            assert_eq!(
+                Err(CpuError::BadOperand { line: 1, operand: String::from("x") }),
+                isa.parse("addx x").map(|_| ())
+            );
+            assert_eq!(
+                Err(CpuError::WrongArity { line: 1, expected: 1, found: 0 }),
+                isa.parse("addx").map(|_| ())
+            );
+        }
+
+        #[test]
+        fn extended_instruction_set() {
+            let isa = InstructionSet::standard()
+                .with_register("y", 0)
+                .with_opcode(Opcode {
+                    name: "mulx",
+                    cycles: 3,
+                    nargs: 1,
+                    exec: |regs, args| regs.set("x", regs["x"] * args[0]),
+                })
+                .with_opcode(Opcode {
+                    name: "movxy",
+                    cycles: 1,
+                    nargs: 0,
+                    exec: |regs, _| regs.set("y", regs["x"]),
+                });
+            let program = isa.parse("addx 1\nmulx 7\nmovxy").unwrap();
+            let mut cpu = Cpu::new(isa, program);
+            cpu.run();
+            assert_eq!(6, cpu.cycle());
+            assert_eq!(Some(14), cpu.regs().get("y"));
+            assert_eq!(None, cpu.regs().get("z"));
+        }
+
+        #[test]
+        fn stepping_and_breakpoints() {
+            let isa = InstructionSet::standard();
+            let program = isa.parse("noop\naddx 3\naddx -5\nnoop").unwrap();
+            let mut cpu = Cpu::new(isa, program);
+
+            assert_eq!(Some(1), cpu.step());
+            assert_eq!(Some(2), cpu.step());
+            assert_eq!((1, 1), (cpu.pc(), cpu.regs()["x"])); // addx is midway
+            assert_eq!(Some(3), cpu.step_instruction());
+            assert_eq!((2, 4), (cpu.pc(), cpu.regs()["x"]));
+
+            cpu.add_breakpoint(Breakpoint::Pc(3));
+            cpu.add_breakpoint(Breakpoint::Cycle(5));
+            assert_eq!(Stop::Break(Breakpoint::Cycle(5)), cpu.run());
+            assert_eq!((4, 2), (cpu.cycle(), cpu.pc()));
+            assert_eq!(Stop::Break(Breakpoint::Pc(3)), cpu.run());
+            assert_eq!((5, -1), (cpu.cycle(), cpu.regs()["x"]));
+            assert_eq!(Stop::Halted, cpu.run());
+            assert_eq!(6, cpu.cycle());
+            assert_eq!(None, cpu.step());
+        }
+
+        #[test]
+        fn trace() {
+            let program = "noop\naddx 3\naddx -5\nnoop";
+            let args = |a: &[&str]| a.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+            assert_eq!(None, super::report(program, &[]));
+            assert_eq!(None, super::report(program, &args(&["trace", "line=2"])));
+            let steps = "\
+cycle 1, pc 1, x = 1
+cycle 3, pc 2, x = 4
+cycle 5, pc 3, x = -1
+cycle 6, pc 4, x = -1
+";
+            assert_eq!(Some(steps.to_string()), super::report(program, &args(&["trace"])));
+            let stops = "\
+Cycle(5): cycle 4, pc 2, x = 4
+Pc(3): cycle 5, pc 3, x = -1
+Halted: cycle 6, pc 4, x = -1
+";
+            let breakpoints = args(&["trace", "pc=3", "cycle=5"]);
+            assert_eq!(Some(stops.to_string()), super::report(program, &breakpoints));
+        }
     }
 }
 
@@ -0,0 +1,152 @@
+// Parsing of the monkey notes:
+//
+// Monkey 0:
+//   Starting items: 79, 98
+//   Operation: new = old * 19
+//   Test: divisible by 23
+//     If true: throw to monkey 2
+//     If false: throw to monkey 3
+
+use super::{Expr, Item, Monkey, Operand, Passer};
+
+// Line numbers start from 1
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub expected: &'static str,
+}
+
+pub fn parse_monkeys(input: &str) -> Result<Vec<Monkey>, ParseError> {
+    let mut notes = Notes {
+        lines: input
+            .lines()
+            .enumerate()
+            .map(|(i, l)| (i + 1, l.trim()))
+            .filter(|(_, l)| !l.is_empty())
+            .collect(),
+        pos: 0,
+    };
+    let mut monkeys = vec![];
+    let mut targets = vec![];
+    let mut next_id = 0;
+    while notes.pos < notes.lines.len() {
+        const MONKEY: &str = "Monkey <n>: (numbered in order)";
+        notes.field("Monkey ", MONKEY, |id| {
+            id.strip_suffix(':')
+                .and_then(|n| n.parse::<usize>().ok())
+                .filter(|n| *n == monkeys.len())
+        })?;
+        let items = notes.field("Starting items:", "Starting items: <n>, ...", |items| {
+            items
+                .split(',')
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(|s| s.parse::<u64>().ok())
+                .collect::<Option<Vec<_>>>()
+        })?;
+        let items = items
+            .into_iter()
+            .map(|worry| {
+                next_id += 1;
+                Item {
+                    id: next_id - 1,
+                    worry,
+                }
+            })
+            .collect();
+        let operation = notes.field(
+            "Operation: new = ",
+            "Operation: new = <old|n> <+|*> <old|n>",
+            parse_expr,
+        )?;
+        let divider = notes.field(
+            "Test: divisible by ",
+            "Test: divisible by <n> (n > 0)",
+            |n| n.parse().ok().filter(|&d| d > 0),
+        )?;
+        let mut target = |notes: &mut Notes, prefix| {
+            let line = notes.line();
+            let target = notes.number(prefix)? as usize;
+            targets.push((line, target));
+            Ok(target)
+        };
+        let target_divisible = target(&mut notes, "If true: throw to monkey ")?;
+        let target_else = target(&mut notes, "If false: throw to monkey ")?;
+        monkeys.push(Monkey::new(
+            items,
+            operation,
+            Passer {
+                divider,
+                target_divisible,
+                target_else,
+            },
+        ));
+    }
+    // only known once every monkey was read
+    if let Some(&(line, _)) = targets.iter().find(|(_, t)| *t >= monkeys.len()) {
+        return Err(ParseError {
+            line,
+            expected: "a monkey from the notes to throw to",
+        });
+    }
+    Ok(monkeys)
+}
+
+// The non-empty lines, with their line numbers
+struct Notes<'a> {
+    lines: Vec<(usize, &'a str)>,
+    pos: usize,
+}
+
+impl<'a> Notes<'a> {
+    // The line number of the next line, or one past the last line at the end
+    fn line(&self) -> usize {
+        match self.lines.get(self.pos) {
+            Some(&(line, _)) => line,
+            None => self.lines.last().map_or(0, |(n, _)| n + 1),
+        }
+    }
+
+    // Parses what follows `prefix` in the next line
+    fn field<T>(
+        &mut self,
+        prefix: &str,
+        expected: &'static str,
+        parse: impl FnOnce(&'a str) -> Option<T>,
+    ) -> Result<T, ParseError> {
+        let (line, l) = (
+            self.line(),
+            self.lines.get(self.pos).map_or("", |&(_, l)| l),
+        );
+        self.pos += 1;
+        l.strip_prefix(prefix)
+            .and_then(|rest| parse(rest.trim()))
+            .ok_or(ParseError { line, expected })
+    }
+
+    fn number(&mut self, prefix: &'static str) -> Result<u64, ParseError> {
+        self.field(prefix, prefix, |n| n.parse().ok())
+    }
+}
+
+fn parse_expr(s: &str) -> Option<Expr> {
+    let mut parts = s.split_whitespace();
+    let lhs = parse_operand(parts.next()?)?;
+    let op = parts.next()?;
+    let rhs = parse_operand(parts.next()?)?;
+    if parts.next().is_some() {
+        return None;
+    }
+    match op {
+        "+" => Some(Expr::Add(lhs, rhs)),
+        "*" => Some(Expr::Mul(lhs, rhs)),
+        _ => None,
+    }
+}
+
+fn parse_operand(s: &str) -> Option<Operand> {
+    match s {
+        "old" => Some(Operand::Old),
+        _ => s.parse().ok().map(Operand::Num),
+    }
+}
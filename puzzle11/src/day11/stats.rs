@@ -0,0 +1,94 @@
+use std::collections::HashMap;
+
+use super::{KeepAway, KeepAwayError, Monkey, Relief, Rules};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoundStats {
+    pub inspections: Vec<u64>, // by each monkey
+    pub throws: Vec<Vec<u64>>, // throws[from][to]
+}
+
+// A single throw of a traced item. Rounds count from 1 and worry is after relief.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Hop {
+    pub round: usize,
+    pub from: usize,
+    pub to: usize,
+    pub worry: u64,
+}
+
+pub struct Record {
+    pub rounds: Vec<RoundStats>,
+    pub routes: HashMap<usize, Vec<Hop>>, // by item id
+}
+
+// Plays the game like `keep_away`, keeping per-round statistics and the routes of the items
+// with the given ids.
+pub fn record(monkeys: Vec<Monkey>, rules: &Rules, traced: &[usize]) -> Result<Record, KeepAwayError> {
+    let n = monkeys.len();
+    let mut game = KeepAway::new(monkeys, rules.relief)?;
+    let mut record = Record {
+        rounds: vec![],
+        routes: traced.iter().map(|&id| (id, vec![])).collect(),
+    };
+    for r in 1..=rules.rounds {
+        let mut stats = RoundStats {
+            inspections: vec![0; n],
+            throws: vec![vec![0; n]; n],
+        };
+        game.round(|from, pass| {
+            stats.inspections[from] += 1;
+            stats.throws[from][pass.target] += 1;
+            if let Some(route) = record.routes.get_mut(&pass.item.id) {
+                route.push(Hop {
+                    round: r,
+                    from,
+                    to: pass.target,
+                    worry: pass.item.worry,
+                });
+            }
+        })?;
+        record.rounds.push(stats);
+    }
+    Ok(record)
+}
+
+// The game's state after round `start` comes up again after round `start + length`, so from
+// then on every `length` rounds repeat the same throws.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Period {
+    pub start: usize,
+    pub length: usize,
+}
+
+// The state is where each worry level is, so this can only be found when worry levels stay
+// bounded: either with no relief (worry is kept modulo the dividers' LCM) or when relief wins.
+pub fn find_period(
+    monkeys: Vec<Monkey>,
+    relief: Relief,
+    max_rounds: usize,
+) -> Result<Option<Period>, KeepAwayError> {
+    let mut game = KeepAway::new(monkeys, relief)?;
+    let mut seen = HashMap::from([(state(&game), 0)]);
+    for r in 1..=max_rounds {
+        game.round(|_, _| ())?;
+        if let Some(start) = seen.insert(state(&game), r) {
+            return Ok(Some(Period {
+                start,
+                length: r - start,
+            }));
+        }
+    }
+    Ok(None)
+}
+
+fn state(game: &KeepAway) -> Vec<Vec<u64>> {
+    game.monkeys
+        .iter()
+        .map(|mnk| {
+            let mut worries: Vec<_> = mnk.items.iter().map(|i| i.worry).collect();
+            worries.sort_unstable();
+            worries
+        })
+        .collect()
+}
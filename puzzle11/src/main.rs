@@ -1,80 +1,276 @@
 fn main() {
+    // `puzzle11 stats part1|part2 [item]...` shows what happens in every round of a part instead,
+    // where the given items go, and whether the throws start repeating
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(report) = day11::report(INPUT, &args) {
+        print!("{report}");
+        return;
+    }
+
     for func in [day11::part1, day11::part2] {
         let start = std::time::Instant::now();
-        let res = func();
+        let res = func(INPUT);
         let dur = start.elapsed().as_nanos();
 
         println!("{res} [{dur} ns]");
     }
 }
 
+const INPUT: &str = include_str!("input.txt");
+
 mod day11 {
-    pub fn part1() -> u64 {
-        let mut monkeys: Vec<Monkey> = get_monkeys();
-        for _ in 0..20 {
-            round(&mut monkeys, &|worry| worry / 3);
-        }
-        monkeys.sort_by_key(|mnk| mnk.nof_throws);
-        let top = monkeys.pop().unwrap();
-        let sec = monkeys.pop().unwrap();
-        top.nof_throws * sec.nof_throws
-    }
-
-    pub fn part2() -> u64 {
-        let mut monkeys: Vec<Monkey> = get_monkeys();
-        for _ in 0..10_000 {
-            round(&mut monkeys, &|worry| {
-                worry % (2u64 * 3 * 5 * 7 * 11 * 13 * 17 * 19)
-            });
-        }
-        monkeys.sort_by_key(|mnk| mnk.nof_throws);
-        let top = monkeys.pop().unwrap();
-        let sec = monkeys.pop().unwrap();
-        top.nof_throws * sec.nof_throws
-    }
-
-    fn round(monkeys: &mut Vec<Monkey>, relief: &impl Fn(u64) -> u64) {
-        for i in 0..monkeys.len() {
-            let passes = monkeys[i].turn(relief);
-            for pass in passes {
-                monkeys[pass.target].items.push(pass.item);
+    mod notes;
+    use notes::parse_monkeys;
+
+    mod stats;
+
+    pub fn part1(input: &str) -> u64 {
+        let monkeys: Vec<Monkey> = parse_monkeys(input).unwrap();
+        keep_away(monkeys, &Rules::PART1).unwrap()
+    }
+
+    pub fn part2(input: &str) -> u64 {
+        let monkeys: Vec<Monkey> = parse_monkeys(input).unwrap();
+        keep_away(monkeys, &Rules::PART2).unwrap()
+    }
+
+    pub fn report(input: &str, args: &[String]) -> Option<String> {
+        let (cmd, rest) = args.split_first()?;
+        let (part, items) = rest.split_first()?;
+        let rules = match (cmd.as_str(), part.as_str()) {
+            ("stats", "part1") => Rules::PART1,
+            ("stats", "part2") => Rules::PART2,
+            _ => return None,
+        };
+        let traced = items
+            .iter()
+            .map(|i| i.parse().ok())
+            .collect::<Option<Vec<usize>>>()?;
+        let monkeys = parse_monkeys(input).unwrap();
+        let rec = stats::record(monkeys, &rules, &traced).unwrap();
+        let mut out = String::new();
+        for (r, round) in rec.rounds.iter().enumerate() {
+            let inspected: Vec<String> = round.inspections.iter().map(u64::to_string).collect();
+            let threw: Vec<String> = round
+                .throws
+                .iter()
+                .enumerate()
+                .flat_map(|(from, to)| to.iter().enumerate().map(move |(to, &n)| (from, to, n)))
+                .filter(|&(_, _, n)| n > 0)
+                .map(|(from, to, n)| format!("{from}->{to} x{n}"))
+                .collect();
+            out += &format!(
+                "round {}: inspected {}, threw {}\n",
+                r + 1,
+                inspected.join(" "),
+                threw.join(", ")
+            );
+        }
+        for id in &traced {
+            let hops: Vec<String> = rec.routes[id]
+                .iter()
+                .map(|h| format!("round {} {}->{} ({})", h.round, h.from, h.to, h.worry))
+                .collect();
+            out += &format!("item {id}: {}\n", hops.join(", "));
+        }
+        let monkeys = parse_monkeys(input).unwrap();
+        out += &match stats::find_period(monkeys, rules.relief, rules.rounds).unwrap() {
+            Some(p) => format!(
+                "the throws repeat every {} rounds after round {}\n",
+                p.length, p.start
+            ),
+            None => format!("the throws don't repeat within {} rounds\n", rules.rounds),
+        };
+        Some(out)
+    }
+
+    #[derive(Debug, Clone, Copy)]
+    enum Relief {
+        // worry goes through the function after each inspection
+        Apply(fn(u64) -> u64),
+        // worry isn't reduced. It's kept modulo the LCM of the monkeys' dividers instead, which
+        // doesn't change any monkey's decision.
+        None,
+    }
+
+    struct Rules {
+        rounds: usize,
+        relief: Relief,
+        top_k: usize, // the monkey business is the product of the top k inspection counts
+    }
+
+    impl Rules {
+        const PART1: Rules = Rules {
+            rounds: 20,
+            relief: Relief::Apply(|worry| worry / 3),
+            top_k: 2,
+        };
+        const PART2: Rules = Rules {
+            rounds: 10_000,
+            relief: Relief::None,
+            top_k: 2,
+        };
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    enum KeepAwayError {
+        // an inspection's result doesn't fit in a u64 (round counts from 1)
+        Overflow { round: usize, monkey: usize, worry: u64 },
+        ModulusOverflow,
+        BusinessOverflow,
+        TooFewMonkeys,
+    }
+
+    fn keep_away(monkeys: Vec<Monkey>, rules: &Rules) -> Result<u64, KeepAwayError> {
+        let mut game = KeepAway::new(monkeys, rules.relief)?;
+        for _ in 0..rules.rounds {
+            game.round(|_, _| ())?;
+        }
+        game.business(rules.top_k)
+    }
+
+    fn lcm(a: u64, b: u64) -> Option<u64> {
+        let gcd = |mut a: u64, mut b: u64| {
+            while b != 0 {
+                (a, b) = (b, a % b);
+            }
+            a
+        };
+        (a / gcd(a, b)).checked_mul(b)
+    }
+
+    struct KeepAway {
+        monkeys: Vec<Monkey>,
+        relief: Relief,
+        modulus: u64,
+        rounds: usize, // played so far
+    }
+
+    impl KeepAway {
+        fn new(monkeys: Vec<Monkey>, relief: Relief) -> Result<Self, KeepAwayError> {
+            let modulus = monkeys
+                .iter()
+                .try_fold(1, |m, mnk| lcm(m, mnk.passer.divider))
+                .ok_or(KeepAwayError::ModulusOverflow)?;
+            Ok(Self {
+                monkeys,
+                relief,
+                modulus,
+                rounds: 0,
+            })
+        }
+
+        // `on_pass` sees every throw as it happens, with the throwing monkey
+        fn round(&mut self, mut on_pass: impl FnMut(usize, &Pass)) -> Result<(), KeepAwayError> {
+            let (relief, modulus) = (self.relief, self.modulus);
+            let relief = |worry: u64| match relief {
+                Relief::Apply(f) => f(worry),
+                Relief::None => worry % modulus,
+            };
+            self.rounds += 1;
+            for i in 0..self.monkeys.len() {
+                let passes = self.monkeys[i]
+                    .turn(&relief)
+                    .map_err(|worry| KeepAwayError::Overflow {
+                        round: self.rounds,
+                        monkey: i,
+                        worry,
+                    })?;
+                for pass in passes {
+                    on_pass(i, &pass);
+                    self.monkeys[pass.target].items.push(pass.item);
+                }
+            }
+            Ok(())
+        }
+
+        fn business(&self, top_k: usize) -> Result<u64, KeepAwayError> {
+            if self.monkeys.len() < top_k {
+                return Err(KeepAwayError::TooFewMonkeys);
+            }
+            let mut throws: Vec<_> = self.monkeys.iter().map(|mnk| mnk.nof_throws).collect();
+            throws.sort_by_key(|&t| std::cmp::Reverse(t));
+            throws[..top_k]
+                .iter()
+                .try_fold(1u64, |business, &t| business.checked_mul(t))
+                .ok_or(KeepAwayError::BusinessOverflow)
+        }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum Operand {
+        Old,
+        Num(u64),
+    }
+
+    // The monkey's operation, `new = <expr>`
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    enum Expr {
+        Add(Operand, Operand),
+        Mul(Operand, Operand),
+    }
+
+    impl Operand {
+        fn eval(self, old: u64) -> u64 {
+            match self {
+                Operand::Old => old,
+                Operand::Num(n) => n,
             }
         }
     }
 
+    impl Expr {
+        // None on overflow
+        fn eval(self, old: u64) -> Option<u64> {
+            match self {
+                Expr::Add(l, r) => l.eval(old).checked_add(r.eval(old)),
+                Expr::Mul(l, r) => l.eval(old).checked_mul(r.eval(old)),
+            }
+        }
+    }
+
+    // Items are numbered in the order they're listed in the notes
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+    struct Item {
+        id: usize,
+        worry: u64,
+    }
+
     struct Monkey {
-        items: Vec<u64>,
-        operation: Box<dyn Fn(u64) -> u64>,
+        items: Vec<Item>,
+        operation: Expr,
         passer: Passer,
         nof_throws: u64,
     }
 
     impl Monkey {
-        fn new<O: Fn(u64) -> u64 + 'static>(items: Vec<u64>, operation: O, passer: Passer) -> Self {
+        fn new(items: Vec<Item>, operation: Expr, passer: Passer) -> Self {
             Self {
                 items,
-                operation: Box::new(operation),
+                operation,
                 passer,
                 nof_throws: 0,
             }
         }
 
-        fn turn(&mut self, relief: &impl Fn(u64) -> u64) -> Vec<Pass> {
+        // Fails with the item's worry level if the operation overflows
+        fn turn(&mut self, relief: &impl Fn(u64) -> u64) -> Result<Vec<Pass>, u64> {
             let mut passes = vec![];
             for item in self.items.drain(..) {
-                let updated_item = relief((self.operation)(item));
-                passes.push(self.passer.pass(updated_item));
+                let worry = relief(self.operation.eval(item.worry).ok_or(item.worry)?);
+                passes.push(self.passer.pass(Item { worry, ..item }));
                 self.nof_throws += 1;
             }
-            passes
+            Ok(passes)
         }
     }
 
     struct Pass {
-        item: u64,
+        item: Item,
         target: usize,
     }
 
+    #[derive(Debug, PartialEq, Eq)]
     struct Passer {
         divider: u64,
         target_divisible: usize,
@@ -82,10 +278,10 @@ mod day11 {
     }
 
     impl Passer {
-        fn pass(&self, n: u64) -> Pass {
+        fn pass(&self, item: Item) -> Pass {
             Pass {
-                item: n,
-                target: if n % self.divider == 0 {
+                item,
+                target: if item.worry.is_multiple_of(self.divider) {
                     self.target_divisible
                 } else {
                     self.target_else
@@ -94,102 +290,224 @@ mod day11 {
         }
     }
 
-    fn get_monkeys() -> Vec<Monkey> {
-        vec![
-            Monkey::new(
-                // 0
-                vec![74, 73, 57, 77, 74],
-                |n| n * 11,
-                Passer {
-                    divider: 19,
-                    target_divisible: 6,
-                    target_else: 7,
-                },
-            ),
-            Monkey::new(
-                // 1
-                vec![99, 77, 79],
-                |n| n + 8,
-                Passer {
-                    divider: 2,
-                    target_divisible: 6,
-                    target_else: 0,
-                },
-            ),
-            Monkey::new(
-                // 2
-                vec![64, 67, 50, 96, 89, 82, 82],
-                |n| n + 1,
-                Passer {
-                    divider: 3,
-                    target_divisible: 5,
-                    target_else: 3,
-                },
-            ),
-            Monkey::new(
-                // 3
-                vec![88],
-                |n| n * 7,
+    #[cfg(test)]
+    mod tests {
+        use super::notes::{parse_monkeys, ParseError};
+        use super::stats::{find_period, record, Hop, Period};
+        use super::{keep_away, KeepAwayError, Relief, Rules};
+        use super::{Expr, Operand, Passer};
+
+        const EXAMPLE: &str = include_str!("example.txt");
+
+        #[test]
+        fn example_part1() {
+            assert_eq!(10605, super::part1(EXAMPLE));
+        }
+
+        #[test]
+        fn real_part1() {
+            assert_eq!(69918, super::part1(crate::INPUT));
+        }
+
+        #[test]
+        fn example_part2() {
+            assert_eq!(2713310158, super::part2(EXAMPLE));
+        }
+
+        #[test]
+        fn real_part2() {
+            assert_eq!(19573408701u64, super::part2(crate::INPUT));
+        }
+
+        #[test]
+        fn parse_example() {
+            let monkeys = parse_monkeys(EXAMPLE).unwrap();
+            assert_eq!(4, monkeys.len());
+            assert_eq!(
+                vec![79, 98],
+                monkeys[0].items.iter().map(|i| i.worry).collect::<Vec<_>>()
+            );
+            assert_eq!(vec![2, 3, 4, 5], monkeys[1].items.iter().map(|i| i.id).collect::<Vec<_>>());
+            assert_eq!(Expr::Mul(Operand::Old, Operand::Num(19)), monkeys[0].operation);
+            assert_eq!(Expr::Mul(Operand::Old, Operand::Old), monkeys[2].operation);
+            assert_eq!(
                 Passer {
                     divider: 17,
-                    target_divisible: 5,
-                    target_else: 4,
-                },
-            ),
-            Monkey::new(
-                // 4
-                vec![80, 66, 98, 83, 70, 63, 57, 66],
-                |n| n + 4,
-                Passer {
-                    divider: 13,
                     target_divisible: 0,
                     target_else: 1,
                 },
-            ),
-            Monkey::new(
-                // 5
-                vec![81, 93, 90, 61, 62, 64],
-                |n| n + 7,
-                Passer {
-                    divider: 7,
-                    target_divisible: 1,
-                    target_else: 4,
-                },
-            ),
-            Monkey::new(
-                // 6
-                vec![69, 97, 88, 93],
-                |n| n * n,
-                Passer {
-                    divider: 5,
-                    target_divisible: 7,
-                    target_else: 2,
-                },
-            ),
-            Monkey::new(
-                // 7
-                vec![59, 80],
-                |n| n + 6,
-                Passer {
-                    divider: 11,
-                    target_divisible: 2,
-                    target_else: 3,
-                },
-            ),
-        ]
-    }
+                monkeys[3].passer
+            );
+            assert_eq!(Some(9), Expr::Add(Operand::Old, Operand::Num(6)).eval(3));
+            assert_eq!(None, Expr::Mul(Operand::Old, Operand::Old).eval(1 << 32));
+        }
 
-    #[cfg(test)]
-    mod tests {
+        #[test]
+        fn parse_errors() {
+            let bad_op = EXAMPLE.replacen("old * 19", "old ^ 19", 1);
+            assert_eq!(
+                Err(ParseError {
+                    line: 3,
+                    expected: "Operation: new = <old|n> <+|*> <old|n>"
+                }),
+                parse_monkeys(&bad_op).map(|_| ())
+            );
+            let truncated = EXAMPLE.lines().take(5).collect::<Vec<_>>().join("\n");
+            assert_eq!(
+                Err(ParseError {
+                    line: 6,
+                    expected: "If false: throw to monkey "
+                }),
+                parse_monkeys(&truncated).map(|_| ())
+            );
+            let misnumbered = EXAMPLE.replacen("Monkey 1:", "Monkey 2:", 1);
+            assert_eq!(8, parse_monkeys(&misnumbered).map(|_| ()).unwrap_err().line);
+            let no_divider = EXAMPLE.replacen("divisible by 23", "divisible by 0", 1);
+            assert_eq!(
+                Err(ParseError {
+                    line: 4,
+                    expected: "Test: divisible by <n> (n > 0)"
+                }),
+                parse_monkeys(&no_divider).map(|_| ())
+            );
+            let no_monkey = EXAMPLE.replacen("throw to monkey 3", "throw to monkey 4", 1);
+            assert_eq!(
+                Err(ParseError {
+                    line: 6,
+                    expected: "a monkey from the notes to throw to"
+                }),
+                parse_monkeys(&no_monkey).map(|_| ())
+            );
+        }
 
         #[test]
-        fn real_part1() {
-            assert_eq!(69918, super::part1());
+        fn custom_rules() {
+            let monkeys = parse_monkeys(EXAMPLE).unwrap();
+            let rules = Rules {
+                rounds: 20,
+                relief: Relief::Apply(|worry| worry / 3),
+                top_k: 3,
+            };
+            assert_eq!(101 * 105 * 95, keep_away(monkeys, &rules).unwrap());
+
+            let monkeys = parse_monkeys(EXAMPLE).unwrap();
+            let rules = Rules {
+                rounds: 1,
+                relief: Relief::None,
+                top_k: 5,
+            };
+            assert_eq!(Err(KeepAwayError::TooFewMonkeys), keep_away(monkeys, &rules));
         }
 
         #[test]
-        fn real_part2() {
-            assert_eq!(19573408701u64, super::part2());
+        fn overflow() {
+            // without relief, worry levels grow until they don't fit
+            let monkeys = parse_monkeys(EXAMPLE).unwrap();
+            let rules = Rules {
+                rounds: 20,
+                relief: Relief::Apply(|worry| worry),
+                top_k: 2,
+            };
+            assert_eq!(
+                Err(KeepAwayError::Overflow {
+                    round: 13,
+                    monkey: 0,
+                    worry: 13988703546165100909,
+                }),
+                keep_away(monkeys, &rules)
+            );
+
+            // reducing modulo the dividers' LCM doesn't help when the LCM itself is too large
+            let big_dividers = EXAMPLE
+                .replace("by 23", "by 4294967311")
+                .replace("by 19", "by 4294967357");
+            let monkeys = parse_monkeys(&big_dividers).unwrap();
+            assert_eq!(
+                Err(KeepAwayError::ModulusOverflow),
+                keep_away(monkeys, &Rules::PART2)
+            );
+        }
+
+        #[test]
+        fn round_stats_and_routes() {
+            let monkeys = parse_monkeys(EXAMPLE).unwrap();
+            let rec = record(monkeys, &Rules::PART1, &[0]).unwrap();
+            assert_eq!(20, rec.rounds.len());
+            let totals: Vec<u64> = (0..4)
+                .map(|m| rec.rounds.iter().map(|r| r.inspections[m]).sum())
+                .collect();
+            assert_eq!(vec![101, 95, 7, 105], totals);
+            assert_eq!(vec![0, 0, 0, 2], rec.rounds[0].throws[0]);
+            for r in &rec.rounds {
+                for (m, throws) in r.throws.iter().enumerate() {
+                    assert_eq!(r.inspections[m], throws.iter().sum::<u64>());
+                }
+            }
+            // item 0 starts as 79 at monkey 0
+            assert_eq!(
+                vec![
+                    Hop { round: 1, from: 0, to: 3, worry: 500 },
+                    Hop { round: 1, from: 3, to: 1, worry: 167 },
+                ],
+                rec.routes[&0][..2]
+            );
+        }
+
+        #[test]
+        fn throw_period() {
+            let monkeys = parse_monkeys(crate::INPUT).unwrap();
+            let period = find_period(monkeys, Relief::None, 1000).unwrap().unwrap();
+            assert_eq!(Period { start: 127, length: 240 }, period);
+
+            // the throws repeat as well, so part 2 can be extrapolated from a few hundred rounds
+            let monkeys = parse_monkeys(crate::INPUT).unwrap();
+            let rules = Rules {
+                rounds: period.start + period.length,
+                ..Rules::PART2
+            };
+            let rec = record(monkeys, &rules, &[]).unwrap();
+            let (prefix, cycle) = rec.rounds.split_at(period.start);
+            let (cycles, rest) = (
+                (10_000 - period.start) / period.length,
+                (10_000 - period.start) % period.length,
+            );
+            let mut inspections: Vec<u64> = (0..8)
+                .map(|m| {
+                    let sum = |rounds: &[super::stats::RoundStats]| {
+                        rounds.iter().map(|r| r.inspections[m]).sum::<u64>()
+                    };
+                    sum(prefix) + cycles as u64 * sum(cycle) + sum(&cycle[..rest])
+                })
+                .collect();
+            inspections.sort_unstable();
+            assert_eq!(super::part2(crate::INPUT), inspections[7] * inspections[6]);
+
+            let monkeys = parse_monkeys(EXAMPLE).unwrap();
+            assert_eq!(None, find_period(monkeys, Relief::None, 1000).unwrap());
+        }
+
+        #[test]
+        fn report() {
+            let args = |a: &[&str]| a.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+            assert_eq!(None, super::report(EXAMPLE, &args(&["stats"])));
+            assert_eq!(None, super::report(EXAMPLE, &args(&["stats", "part3"])));
+            let bad_item = args(&["stats", "part1", "x"]);
+            assert_eq!(None, super::report(EXAMPLE, &bad_item));
+
+            let report = super::report(EXAMPLE, &args(&["stats", "part1", "0"])).unwrap();
+            let lines: Vec<&str> = report.lines().collect();
+            assert_eq!(22, lines.len());
+            assert_eq!(
+                "round 1: inspected 2 4 3 5, threw 0->3 x2, 1->0 x4, 2->1 x1, 2->3 x2, 3->1 x5",
+                lines[0]
+            );
+            assert!(lines[20].starts_with("item 0: round 1 0->3 (500), round 1 3->1 (167), "));
+
+            let report = super::report(crate::INPUT, &args(&["stats", "part2"])).unwrap();
+            assert_eq!(
+                Some("the throws repeat every 240 rounds after round 127"),
+                report.lines().last()
+            );
         }
     }
 }
@@ -0,0 +1,134 @@
+// Lets petgraph's algorithms walk a HillMap without building a node per cell.
+
+use std::collections::HashSet;
+
+use petgraph::visit::{Data, GraphBase, IntoEdgeReferences, IntoEdges, IntoNeighbors, Visitable};
+
+use super::{Cell, ClimbRule, HillMap};
+
+const DIRS: [(isize, isize); 4] = [(0, 1), (1, 0), (0, -1), (-1, 0)];
+
+// Every step costs the same
+const STEP: u32 = 1;
+
+impl<R> GraphBase for HillMap<'_, R> {
+    type NodeId = Cell;
+    type EdgeId = (Cell, Cell);
+}
+
+impl<R> Data for HillMap<'_, R> {
+    type NodeWeight = u32;
+    type EdgeWeight = u32;
+}
+
+impl<R> Visitable for HillMap<'_, R> {
+    type Map = HashSet<Cell>;
+
+    fn visit_map(&self) -> Self::Map {
+        HashSet::new()
+    }
+
+    fn reset_map(&self, map: &mut Self::Map) {
+        map.clear();
+    }
+}
+
+// The cells that can be stepped to from `from`
+pub struct Steps<'a, 'm, R> {
+    map: &'a HillMap<'m, R>,
+    from: Cell,
+    dir: usize,
+}
+
+impl<'a, 'm, R> Steps<'a, 'm, R> {
+    pub fn new(map: &'a HillMap<'m, R>, from: Cell) -> Self {
+        Self { map, from, dir: 0 }
+    }
+}
+
+impl<R: ClimbRule> Iterator for Steps<'_, '_, R> {
+    type Item = Cell;
+
+    fn next(&mut self) -> Option<Cell> {
+        let heights = self.map.heights;
+        let (r, c) = self.from;
+        while let Some(&(dr, dc)) = DIRS.get(self.dir) {
+            self.dir += 1;
+            let Some(nr) = r.checked_add_signed(dr).filter(|&nr| nr < heights.rows) else {
+                continue;
+            };
+            let Some(nc) = c.checked_add_signed(dc).filter(|&nc| nc < heights.cols) else {
+                continue;
+            };
+            let to = (nr, nc);
+            if self.map.rule.allows(heights.height(self.from), heights.height(to)) {
+                return Some(to);
+            }
+        }
+        None
+    }
+}
+
+pub struct Edges<'a, 'm, R>(Steps<'a, 'm, R>);
+
+impl<R: ClimbRule> Iterator for Edges<'_, '_, R> {
+    type Item = (Cell, Cell, &'static u32);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.0.next().map(|to| (self.0.from, to, &STEP))
+    }
+}
+
+pub struct AllEdges<'a, 'm, R> {
+    map: &'a HillMap<'m, R>,
+    next_cell: usize,
+    edges: Option<Edges<'a, 'm, R>>,
+}
+
+impl<R: ClimbRule> Iterator for AllEdges<'_, '_, R> {
+    type Item = (Cell, Cell, &'static u32);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(edge) = self.edges.as_mut().and_then(Iterator::next) {
+                return Some(edge);
+            }
+            let heights = self.map.heights;
+            if self.next_cell == heights.heights.len() {
+                return None;
+            }
+            let cell = (self.next_cell / heights.cols, self.next_cell % heights.cols);
+            self.edges = Some(Edges(Steps::new(self.map, cell)));
+            self.next_cell += 1;
+        }
+    }
+}
+
+impl<'a, 'm, R: ClimbRule> IntoNeighbors for &'a HillMap<'m, R> {
+    type Neighbors = Steps<'a, 'm, R>;
+
+    fn neighbors(self, a: Cell) -> Self::Neighbors {
+        Steps::new(self, a)
+    }
+}
+
+impl<'a, 'm, R: ClimbRule> IntoEdgeReferences for &'a HillMap<'m, R> {
+    type EdgeRef = (Cell, Cell, &'static u32);
+    type EdgeReferences = AllEdges<'a, 'm, R>;
+
+    fn edge_references(self) -> Self::EdgeReferences {
+        AllEdges {
+            map: self,
+            next_cell: 0,
+            edges: None,
+        }
+    }
+}
+
+impl<'a, 'm, R: ClimbRule> IntoEdges for &'a HillMap<'m, R> {
+    type Edges = Edges<'a, 'm, R>;
+
+    fn edges(self, a: Cell) -> Self::Edges {
+        Edges(Steps::new(self, a))
+    }
+}
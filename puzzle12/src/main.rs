@@ -1,100 +1,454 @@
 fn main() {
+    // `puzzle12 route [astar] [ascent|descent|symmetric=N]` draws the shortest route from S to E
+    // instead, climbing by the given rule (`ascent=1` by default)
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(route) = day12::report(INPUT, &args) {
+        print!("{route}");
+        return;
+    }
+
     for func in [day12::part1, day12::part2] {
         let start = std::time::Instant::now();
         let res = func(INPUT);
         let dur = start.elapsed().as_nanos();
 
-        println!("{res} [{dur} ns]");
+        match res {
+            Some(steps) => println!("{steps} [{dur} ns]"),
+            None => println!("E can't be reached [{dur} ns]"),
+        }
     }
 }
 
 mod day12 {
-    use petgraph::algo::dijkstra::dijkstra;
-    use petgraph::prelude::*;
+    use std::collections::VecDeque;
+    use std::fmt;
+
+    use petgraph::algo::astar;
+    use petgraph::visit::EdgeRef;
 
-    pub fn part1(input: &str) -> u64 {
+    mod graph;
+
+    pub fn part1(input: &str) -> Option<u64> {
         // classic shortest path problem
-        let (height_graph, starti, endi) = build_graph(input);
-        let sssp = dijkstra(&height_graph, starti, Some(endi), |_| 1);
-        *sssp.get(&endi).unwrap() as u64
-    }
-
-    pub fn part2(input: &str) -> u64 {
-        // classic single-source shortest paths problem on the inverse graph
-        let (mut height_graph, _, endi) = build_graph(input);
-        height_graph.reverse();
-        let sssp = dijkstra(&height_graph, endi, None, |_| 1);
-        *sssp
-            .iter()
-            .filter(|(&ni, &_)| *height_graph.node_weight(ni).unwrap() == 0)
-            .min_by_key(|(&_, &len)| len)
-            .unwrap()
-            .1
-    }
-
-    fn build_graph(input: &str) -> (DiGraph<u32, u32>, NodeIndex, NodeIndex) {
-        let mut nodes = vec![];
-        let mut graph = DiGraph::new();
-        let mut starti = NodeIndex::default();
-        let mut endi = NodeIndex::default();
-        for l in input.lines() {
-            let mut row = vec![];
-            for c in l.chars() {
-                let height = match &c {
-                    'S' => 0,
-                    'E' => 'z' as u32 - 'a' as u32,
-                    _ => c as u32 - 'a' as u32,
-                };
-                let n = graph.add_node(height);
-                if c == 'S' {
-                    starti = n;
+        let heights = HeightMap::parse(input).unwrap_or_else(|e| panic!("{e}"));
+        let map = HillMap::new(&heights, Climb::MaxAscent(1));
+        let path = map.shortest_path(heights.start, heights.end);
+        path.map(|p| p.steps() as u64)
+    }
+
+    pub fn part2(input: &str) -> Option<u64> {
+        // multi-source shortest path, from every 'a' at once
+        let heights = HeightMap::parse(input).unwrap_or_else(|e| panic!("{e}"));
+        let map = HillMap::new(&heights, Climb::MaxAscent(1));
+        let lowest = heights.cells().filter(|&c| heights.height(c) == 0);
+        let path = map.shortest_path_between(lowest, |c| c == heights.end);
+        path.map(|p| p.steps() as u64)
+    }
+
+    // The route from S to E drawn over the map, found breadth first or with A*
+    pub fn report(input: &str, args: &[String]) -> Option<String> {
+        let (cmd, rest) = args.split_first()?;
+        if cmd != "route" {
+            return None;
+        }
+        let mut astar = false;
+        let mut climb = Climb::MaxAscent(1);
+        for arg in rest {
+            if arg == "astar" {
+                astar = true;
+                continue;
+            }
+            let (rule, n) = arg.split_once('=')?;
+            let n = n.parse().ok()?;
+            climb = match rule {
+                "ascent" => Climb::MaxAscent(n),
+                "descent" => Climb::MaxDescent(n),
+                "symmetric" => Climb::Symmetric(n),
+                _ => return None,
+            };
+        }
+        let heights = HeightMap::parse(input).unwrap_or_else(|e| panic!("{e}"));
+        let map = HillMap::new(&heights, climb);
+        let path = if astar {
+            map.astar(heights.start, heights.end)
+        } else {
+            map.shortest_path(heights.start, heights.end)
+        };
+        Some(match path {
+            Some(path) => format!("{}{} steps\n", heights.render(&path), path.steps()),
+            None => String::from("E can't be reached\n"),
+        })
+    }
+
+    // (row, column)
+    pub type Cell = (usize, usize);
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    struct Path {
+        cells: Vec<Cell>,
+    }
+
+    impl Path {
+        fn steps(&self) -> usize {
+            self.cells.len() - 1
+        }
+    }
+
+    // Heights are 0 ('a') to 25 ('z'), stored row by row
+    pub struct HeightMap {
+        heights: Vec<u32>,
+        rows: usize,
+        cols: usize,
+        start: Cell,
+        end: Cell,
+    }
+
+    // Lines and columns are 1-based
+    #[derive(Debug, PartialEq, Eq)]
+    pub enum ParseError {
+        Empty,
+        // Not a lowercase letter, S or E
+        BadHeight { line: usize, col: usize },
+        // A row of a different length than the first one
+        Ragged { line: usize },
+        NoStart,
+        NoEnd,
+    }
+
+    impl fmt::Display for ParseError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                ParseError::Empty => write!(f, "the height map is empty"),
+                ParseError::BadHeight { line, col } => {
+                    write!(f, "line {line}, column {col}: not a height, S or E")
                 }
-                if c == 'E' {
-                    endi = n;
+                ParseError::Ragged { line } => {
+                    write!(f, "line {line} isn't as long as the first one")
                 }
-                row.push(n);
+                ParseError::NoStart => write!(f, "there's no S to start from"),
+                ParseError::NoEnd => write!(f, "there's no E to get to"),
             }
-            nodes.push(row)
-        }
-        // There's an edge betwen n1 to n2 if they're adjacent and n2 can be climbed to or
-        // descended from n1 (n2 height is at most n1's height + 1)
-        for r in 0..nodes.len() {
-            for c in 0..nodes[0].len() {
-                if c > 0 {
-                    let cur = *graph.node_weight(nodes[r][c]).unwrap();
-                    let prev = *graph.node_weight(nodes[r][c - 1]).unwrap();
-                    if prev <= cur + 1 {
-                        graph.add_edge(nodes[r][c], nodes[r][c - 1], 1);
-                    }
-                    if cur <= prev + 1 {
-                        graph.add_edge(nodes[r][c - 1], nodes[r][c], 1);
-                    }
+        }
+    }
+
+    impl HeightMap {
+        fn parse(input: &str) -> Result<Self, ParseError> {
+            let mut heights = vec![];
+            let mut start = None;
+            let mut end = None;
+            let mut rows = 0;
+            let mut cols = 0;
+            for (r, l) in input.lines().enumerate() {
+                for (c, ch) in l.chars().enumerate() {
+                    let height = match ch {
+                        'S' => {
+                            start = Some((r, c));
+                            0
+                        }
+                        'E' => {
+                            end = Some((r, c));
+                            'z' as u32 - 'a' as u32
+                        }
+                        'a'..='z' => ch as u32 - 'a' as u32,
+                        _ => {
+                            return Err(ParseError::BadHeight {
+                                line: r + 1,
+                                col: c + 1,
+                            })
+                        }
+                    };
+                    heights.push(height);
+                }
+                if r == 0 {
+                    cols = heights.len();
+                } else if heights.len() != (r + 1) * cols {
+                    return Err(ParseError::Ragged { line: r + 1 });
                 }
-                if r > 0 {
-                    let cur = *graph.node_weight(nodes[r][c]).unwrap();
-                    let prev = *graph.node_weight(nodes[r - 1][c]).unwrap();
-                    if prev <= cur + 1 {
-                        graph.add_edge(nodes[r][c], nodes[r - 1][c], 1);
+                rows += 1;
+            }
+            if heights.is_empty() {
+                return Err(ParseError::Empty);
+            }
+            Ok(Self {
+                cols,
+                heights,
+                rows,
+                start: start.ok_or(ParseError::NoStart)?,
+                end: end.ok_or(ParseError::NoEnd)?,
+            })
+        }
+
+        fn height(&self, (r, c): Cell) -> u32 {
+            self.heights[self.index((r, c))]
+        }
+
+        fn index(&self, (r, c): Cell) -> usize {
+            r * self.cols + c
+        }
+
+        fn cells(&self) -> impl Iterator<Item = Cell> + '_ {
+            (0..self.rows).flat_map(move |r| (0..self.cols).map(move |c| (r, c)))
+        }
+
+        // The path drawn as arrows towards its end (E), like the puzzle draws it. Cells off the
+        // path are '.', as a 'v' arrow could pass for a height.
+        fn render(&self, path: &Path) -> String {
+            let mut grid = vec![vec!['.'; self.cols]; self.rows];
+            for step in path.cells.windows(2) {
+                let ((r, c), (nr, nc)) = (step[0], step[1]);
+                grid[r][c] = match (nr as isize - r as isize, nc as isize - c as isize) {
+                    (0, 1) => '>',
+                    (0, -1) => '<',
+                    (1, 0) => 'v',
+                    (-1, 0) => '^',
+                    _ => unreachable!(),
+                };
+            }
+            if let Some(&(r, c)) = path.cells.last() {
+                grid[r][c] = 'E';
+            }
+            grid.into_iter()
+                .map(|row| row.into_iter().collect::<String>() + "\n")
+                .collect()
+        }
+    }
+
+    // Decides whether a step from one height to another is allowed
+    pub trait ClimbRule {
+        fn allows(&self, from: u32, to: u32) -> bool;
+    }
+
+    #[derive(Debug, Clone, Copy)]
+    pub enum Climb {
+        // Up by at most that much, down by any amount
+        MaxAscent(u32),
+        // Down by at most that much, up by any amount
+        MaxDescent(u32),
+        // Up or down by at most that much
+        Symmetric(u32),
+    }
+
+    impl ClimbRule for Climb {
+        fn allows(&self, from: u32, to: u32) -> bool {
+            match *self {
+                Climb::MaxAscent(n) => to <= from + n,
+                Climb::MaxDescent(n) => from <= to + n,
+                Climb::Symmetric(n) => from.abs_diff(to) <= n,
+            }
+        }
+    }
+
+    impl<F: Fn(u32, u32) -> bool> ClimbRule for F {
+        fn allows(&self, from: u32, to: u32) -> bool {
+            self(from, to)
+        }
+    }
+
+    // The graph of allowed steps over a height map. Edges are found on the fly rather than
+    // stored, and `&HillMap` implements the petgraph traits its algorithms need.
+    pub struct HillMap<'m, R> {
+        heights: &'m HeightMap,
+        rule: R,
+    }
+
+    impl<'m, R: ClimbRule> HillMap<'m, R> {
+        fn new(heights: &'m HeightMap, rule: R) -> Self {
+            Self { heights, rule }
+        }
+
+        fn shortest_path(&self, from: Cell, to: Cell) -> Option<Path> {
+            self.shortest_path_between([from], |c| c == to)
+        }
+
+        // The shortest of the paths from any of the sources to any target, breadth first
+        // (all steps cost 1)
+        fn shortest_path_between(
+            &self,
+            sources: impl IntoIterator<Item = Cell>,
+            is_target: impl Fn(Cell) -> bool,
+        ) -> Option<Path> {
+            let mut prev = vec![None; self.heights.heights.len()];
+            let mut queue = VecDeque::new();
+            for source in sources {
+                prev[self.heights.index(source)] = Some(source);
+                queue.push_back(source);
+            }
+            while let Some(cell) = queue.pop_front() {
+                if is_target(cell) {
+                    let mut cells = vec![cell];
+                    let mut cur = cell;
+                    while let Some(p) = prev[self.heights.index(cur)].filter(|&p| p != cur) {
+                        cells.push(p);
+                        cur = p;
                     }
-                    if cur <= prev + 1 {
-                        graph.add_edge(nodes[r - 1][c], nodes[r][c], 1);
+                    cells.reverse();
+                    return Some(Path { cells });
+                }
+                for next in graph::Steps::new(self, cell) {
+                    let seen = &mut prev[self.heights.index(next)];
+                    if seen.is_none() {
+                        *seen = Some(cell);
+                        queue.push_back(next);
                     }
                 }
             }
+            None
+        }
+
+        // Single target search, guided by the Manhattan distance to it
+        fn astar(&self, from: Cell, to: Cell) -> Option<Path> {
+            let (_, cells) = astar(
+                self,
+                from,
+                |c| c == to,
+                |e| *e.weight(),
+                |(r, c)| (r.abs_diff(to.0) + c.abs_diff(to.1)) as u32,
+            )?;
+            Some(Path { cells })
         }
-        (graph, starti, endi)
     }
 
     #[cfg(test)]
     mod tests {
+        use super::{Climb, HeightMap, HillMap, ParseError};
+
+        const EXAMPLE: &str = "\
+Sabqponm
+abcryxxl
+accszExk
+acctuvwj
+abdefghi";
+
+        #[test]
+        fn example_part1() {
+            assert_eq!(Some(31), super::part1(EXAMPLE));
+        }
+
+        #[test]
+        fn example_part2() {
+            assert_eq!(Some(29), super::part2(EXAMPLE));
+        }
+
         #[test]
         fn real_part1() {
-            assert_eq!(420, super::part1(crate::INPUT));
+            assert_eq!(Some(420), super::part1(crate::INPUT));
         }
 
         #[test]
         fn real_part2() {
-            assert_eq!(414, super::part2(crate::INPUT));
+            assert_eq!(Some(414), super::part2(crate::INPUT));
+        }
+
+        #[test]
+        fn route() {
+            let heights = HeightMap::parse(EXAMPLE).unwrap();
+            let map = HillMap::new(&heights, Climb::MaxAscent(1));
+            let path = map.shortest_path(heights.start, heights.end).unwrap();
+            assert_eq!(31, path.steps());
+            assert_eq!(Some(&(0, 0)), path.cells.first());
+            assert_eq!(Some(&(2, 5)), path.cells.last());
+            for step in path.cells.windows(2) {
+                let ((r, c), (nr, nc)) = (step[0], step[1]);
+                assert_eq!(1, r.abs_diff(nr) + c.abs_diff(nc));
+            }
+            let drawn = "\
+>>vv<<<<
+..vvv<<^
+..vv>E^^
+..v>>>^^
+..>>>>>^
+";
+            assert_eq!(drawn, heights.render(&path));
+
+            let args = |a: &[&str]| a.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+            assert_eq!(None, super::report(EXAMPLE, &[]));
+            assert_eq!(None, super::report(EXAMPLE, &args(&["route", "dfs"])));
+            let report = format!("{drawn}31 steps\n");
+            assert_eq!(Some(report.clone()), super::report(EXAMPLE, &args(&["route"])));
+            let astar = super::report(EXAMPLE, &args(&["route", "astar"])).unwrap();
+            // maybe another route, as short
+            assert_eq!(6, astar.lines().count());
+            assert!(astar.ends_with("\n31 steps\n"));
+            assert_eq!(
+                Some(String::from("E can't be reached\n")),
+                super::report("Sbz\nbbE", &args(&["route", "astar"]))
+            );
+        }
+
+        #[test]
+        fn astar_route() {
+            let heights = HeightMap::parse(crate::INPUT).unwrap();
+            let map = HillMap::new(&heights, Climb::MaxAscent(1));
+            let bfs = map.shortest_path(heights.start, heights.end).unwrap();
+            let astar = map.astar(heights.start, heights.end).unwrap();
+            assert_eq!(bfs.steps(), astar.steps());
+            assert_eq!(heights.start, astar.cells[0]);
+        }
+
+        #[test]
+        fn unreachable() {
+            let heights = HeightMap::parse("Sbz\nbbE").unwrap();
+            let map = HillMap::new(&heights, Climb::MaxAscent(1));
+            assert_eq!(None, map.shortest_path(heights.start, heights.end));
+            assert_eq!(None, map.astar(heights.start, heights.end));
+            let lowest = heights.cells().filter(|&c| heights.height(c) == 0);
+            assert_eq!(None, map.shortest_path_between(lowest, |c| c == heights.end));
+            assert_eq!(None, super::part1("Sbz\nbbE"));
+            assert_eq!(None, super::part2("Sbz\nbbE"));
+        }
+
+        #[test]
+        fn parse_errors() {
+            let parse = |input| HeightMap::parse(input).map(|_| ());
+            assert_eq!(Err(ParseError::Empty), parse(""));
+            assert_eq!(Err(ParseError::BadHeight { line: 2, col: 2 }), parse("Sb\nb1E"));
+            assert_eq!(Err(ParseError::BadHeight { line: 1, col: 3 }), parse("SbA\nbbE"));
+            assert_eq!(Err(ParseError::Ragged { line: 2 }), parse("Sbc\nbE"));
+            assert_eq!(Err(ParseError::NoStart), parse("abc\nbbE"));
+            assert_eq!(Err(ParseError::NoEnd), parse("Sbc\nbbz"));
+            assert_eq!("there's no E to get to", ParseError::NoEnd.to_string());
+        }
+
+        #[test]
+        fn climb_rules() {
+            let heights = HeightMap::parse(EXAMPLE).unwrap();
+            // walking down from E with at most one step down is the same route backwards
+            let down = HillMap::new(&heights, Climb::MaxDescent(1));
+            let path = down.shortest_path(heights.end, heights.start).unwrap();
+            assert_eq!(31, path.steps());
+            let nearest = down.shortest_path_between([heights.end], |c| heights.height(c) == 0);
+            assert_eq!(29, nearest.unwrap().steps());
+
+            let level = HillMap::new(&heights, Climb::Symmetric(0));
+            assert_eq!(4, level.shortest_path((0, 0), (4, 0)).unwrap().steps());
+            assert_eq!(None, level.shortest_path((0, 0), (0, 2)));
+            let gentle = HillMap::new(&heights, Climb::Symmetric(1));
+            assert_eq!(11, gentle.shortest_path((0, 0), (4, 7)).unwrap().steps());
+
+            let args = |a: &[&str]| a.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+            let level = super::report(EXAMPLE, &args(&["route", "symmetric=0"]));
+            assert_eq!(Some(String::from("E can't be reached\n")), level);
+            let down = super::report(EXAMPLE, &args(&["route", "descent=1", "astar"])).unwrap();
+            assert!(down.ends_with("\n7 steps\n"));
+            assert_eq!(None, super::report(EXAMPLE, &args(&["route", "sideways=1"])));
+            assert_eq!(None, super::report(EXAMPLE, &args(&["route", "ascent=-1"])));
+
+            let free = HillMap::new(&heights, |_, _| true);
+            assert_eq!(7, free.shortest_path(heights.start, heights.end).unwrap().steps());
+            assert_eq!(7, free.astar(heights.start, heights.end).unwrap().steps());
+        }
+
+        #[test]
+        fn multiple_targets() {
+            let heights = HeightMap::parse(EXAMPLE).unwrap();
+            let map = HillMap::new(&heights, Climb::MaxAscent(1));
+            // from both ends of the bottom row to whichever 'x' is closest
+            let path = map
+                .shortest_path_between([(4, 0), (4, 7)], |c| heights.height(c) == 23)
+                .unwrap();
+            assert_eq!(Some(&(4, 7)), path.cells.first());
+            assert_eq!(Some(&(2, 6)), path.cells.last());
+            assert_eq!(15, path.steps());
         }
     }
 }
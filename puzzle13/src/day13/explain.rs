@@ -0,0 +1,156 @@
+// Packet comparison, with an optional trace of how the ordering was decided.
+//
+// An int compared against a list is promoted by viewing it as a one item slice, so comparing
+// never allocates. `Pkt::compare` runs it with the `()` trace, which records nothing.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::slice;
+
+use num_bigint::BigInt;
+
+use super::Pkt;
+
+pub trait Trace<'a> {
+    // Descending into the items at `index` of both lists
+    fn enter(&mut self, _index: usize) {}
+    // Those items were equal, back to their lists
+    fn leave(&mut self) {}
+    fn promote(&mut self, _side: Side) {}
+    fn decide(&mut self, _rule: Rule<'a>) {}
+}
+
+impl Trace<'_> for () {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rule<'a> {
+    // Two different ints
+    Ints(&'a BigInt, &'a BigInt),
+    // One list ran out of items first, with both lists' lengths
+    Lengths(usize, usize),
+    // Nothing differed
+    Identical,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Promotion {
+    pub path: Vec<usize>,
+    pub side: Side,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Explanation<'a> {
+    pub ordering: Ordering,
+    // Indices from the outer lists down to where the rule decided
+    pub path: Vec<usize>,
+    pub promotions: Vec<Promotion>,
+    pub rule: Rule<'a>,
+}
+
+impl<'a> Trace<'a> for Explanation<'a> {
+    fn enter(&mut self, index: usize) {
+        self.path.push(index);
+    }
+
+    fn leave(&mut self) {
+        self.path.pop();
+    }
+
+    fn promote(&mut self, side: Side) {
+        self.promotions.push(Promotion {
+            path: self.path.clone(),
+            side,
+        });
+    }
+
+    fn decide(&mut self, rule: Rule<'a>) {
+        self.rule = rule;
+    }
+}
+
+impl Pkt {
+    // The puzzle's order: ints against ints, lists item by item, and an int against a list as
+    // a list of just that int
+    pub fn compare(&self, other: &Pkt) -> Ordering {
+        compare(self, other, &mut ())
+    }
+
+    pub fn explain<'a>(&'a self, other: &'a Pkt) -> Explanation<'a> {
+        let mut explanation = Explanation {
+            ordering: Ordering::Equal,
+            path: vec![],
+            promotions: vec![],
+            rule: Rule::Identical,
+        };
+        explanation.ordering = compare(self, other, &mut explanation);
+        explanation
+    }
+}
+
+pub fn compare<'a>(left: &'a Pkt, right: &'a Pkt, trace: &mut impl Trace<'a>) -> Ordering {
+    use Pkt::{Int, List};
+    match (left, right) {
+        (Int(l), Int(r)) => {
+            let ordering = l.cmp(r);
+            if ordering.is_ne() {
+                trace.decide(Rule::Ints(l, r));
+            }
+            ordering
+        }
+        (Int(_), List(r)) => {
+            trace.promote(Side::Left);
+            compare_lists(slice::from_ref(left), r, trace)
+        }
+        (List(l), Int(_)) => {
+            trace.promote(Side::Right);
+            compare_lists(l, slice::from_ref(right), trace)
+        }
+        (List(l), List(r)) => compare_lists(l, r, trace),
+    }
+}
+
+fn compare_lists<'a>(left: &'a [Pkt], right: &'a [Pkt], trace: &mut impl Trace<'a>) -> Ordering {
+    for (i, (l, r)) in left.iter().zip(right).enumerate() {
+        trace.enter(i);
+        match compare(l, r, trace) {
+            Ordering::Equal => trace.leave(),
+            difference => return difference,
+        }
+    }
+    let ordering = left.len().cmp(&right.len());
+    if ordering.is_ne() {
+        trace.decide(Rule::Lengths(left.len(), right.len()));
+    }
+    ordering
+}
+
+impl fmt::Display for Explanation<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.ordering {
+            Ordering::Less => write!(f, "right order")?,
+            Ordering::Greater => write!(f, "wrong order")?,
+            Ordering::Equal => write!(f, "equal")?,
+        }
+        match self.rule {
+            Rule::Ints(l, r) => write!(f, ", {l} vs {r}")?,
+            Rule::Lengths(l, r) if l < r => write!(f, ", left ran out of items ({l} vs {r})")?,
+            Rule::Lengths(l, r) => write!(f, ", right ran out of items ({l} vs {r})")?,
+            Rule::Identical => {}
+        }
+        write!(f, " at {:?}", self.path)?;
+        for p in &self.promotions {
+            let side = match p.side {
+                Side::Left => "left",
+                Side::Right => "right",
+            };
+            write!(f, ", promoted {side} to a list at {:?}", p.path)?;
+        }
+        Ok(())
+    }
+}
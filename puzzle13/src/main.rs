@@ -1,173 +1,447 @@
 fn main() {
+    // `puzzle13 explain [all]` tells why the pairs in the wrong order are, or why each pair is
+    // ordered the way it is, instead
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(report) = day13::report(INPUT, &args) {
+        print!("{report}");
+        return;
+    }
+
     for func in [day13::part1, day13::part2] {
         let start = std::time::Instant::now();
-        let res = func();
+        let res = func(INPUT);
         let dur = start.elapsed().as_nanos();
 
         println!("{res} [{dur} ns]");
     }
 }
 
+const INPUT: &str = include_str!("input.txt");
+
 mod day13 {
-    pub fn part1() -> u64 {
-        let pairs = parse_pairs();
+    use std::fmt;
+    use std::str::FromStr;
+
+    use num_bigint::BigInt;
+
+    mod explain;
+
+    pub fn part1(input: &str) -> u64 {
+        let pairs = parse_pairs(input).unwrap();
         pairs
             .iter()
             .enumerate()
-            .filter(|(_, (v1, v2))| v1 < v2)
+            .filter(|(_, (v1, v2))| v1.compare(v2).is_lt())
             .map(|(idx, _)| idx as u64 + 1u64)
             .sum()
     }
 
-    pub fn part2() -> u64 {
-        let mut all = collect_packets();
-        let two = Pkt::from("[[2]]");
-        let six = Pkt::from("[[6]]");
-        all.push(two.clone());
-        all.push(six.clone());
-        all.sort();
-        let mut twoi = usize::default();
-        let mut sixi = usize::default();
-        for (i, pkt) in all.iter().enumerate() {
-            if *pkt == two {
-                twoi = i + 1;
-            }
-            if *pkt == six {
-                sixi = i + 1;
+    pub fn part2(input: &str) -> u64 {
+        let packets = collect_packets(input).unwrap();
+        let dividers = ["[[2]]", "[[6]]"].map(|s| s.parse().unwrap());
+        decoder_key(&packets, &dividers)
+    }
+
+    pub fn report(input: &str, args: &[String]) -> Option<String> {
+        let every = match args {
+            [cmd] if cmd == "explain" => false,
+            [cmd, all] if cmd == "explain" && all == "all" => true,
+            _ => return None,
+        };
+        let pairs = parse_pairs(input).unwrap();
+        let mut out = String::new();
+        for (i, (l, r)) in pairs.iter().enumerate() {
+            let why = l.explain(r);
+            if every || why.ordering.is_gt() {
+                out += &format!("pair {}: {why}\n", i + 1);
             }
         }
-        twoi as u64 * sixi as u64
+        Some(out)
+    }
+
+    // The product of the dividers' 1-based positions once sorted in with the packets. Each
+    // position is found by counting the packets before it, without sorting.
+    pub fn decoder_key(packets: &[Pkt], dividers: &[Pkt]) -> u64 {
+        dividers
+            .iter()
+            .map(|divider| {
+                let before = packets
+                    .iter()
+                    .chain(dividers)
+                    .filter(|p| p.compare(divider).is_lt())
+                    .count();
+                before as u64 + 1
+            })
+            .product()
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    pub enum InputError {
+        Packet { line: usize, err: ParseError },
+        // A packet left without a pair before a blank line or the end of input
+        Unpaired { line: usize },
     }
 
-    fn parse_pairs() -> Vec<(Pkt, Pkt)> {
-        let mut lines = concat!("\n", include_str!("input.txt")).lines();
+    // Pairs are separated by blank lines, though a missing or extra blank line is fine as long
+    // as the packets still pair up
+    fn parse_pairs(input: &str) -> Result<Vec<(Pkt, Pkt)>, InputError> {
         let mut res = vec![];
-        while lines.next().is_some() { // skips the empty line
-            res.push((Pkt::from(lines.next().unwrap()), Pkt::from(lines.next().unwrap())));
+        let mut first = None;
+        for (i, l) in input.lines().enumerate() {
+            if l.trim().is_empty() {
+                if let Some((_, line)) = first {
+                    return Err(InputError::Unpaired { line });
+                }
+                continue;
+            }
+            let pkt = parse_line(l, i + 1)?;
+            match first.take() {
+                None => first = Some((pkt, i + 1)),
+                Some((left, _)) => res.push((left, pkt)),
+            }
+        }
+        match first {
+            None => Ok(res),
+            Some((_, line)) => Err(InputError::Unpaired { line }),
         }
-        res
     }
 
-    fn collect_packets() -> Vec<Pkt> {
-        let lines = include_str!("input.txt").lines();
-        lines.filter(|s| !s.is_empty()).map(Pkt::from).collect()
+    fn collect_packets(input: &str) -> Result<Vec<Pkt>, InputError> {
+        input
+            .lines()
+            .enumerate()
+            .filter(|(_, l)| !l.trim().is_empty())
+            .map(|(i, l)| parse_line(l, i + 1))
+            .collect()
+    }
+
+    fn parse_line(l: &str, line: usize) -> Result<Pkt, InputError> {
+        l.trim()
+            .parse()
+            .map_err(|err| InputError::Packet { line, err })
     }
 
-    #[allow(clippy::derive_ord_xor_partial_ord)]
-    #[derive(Debug, Eq, Ord, Clone)]
-    enum Pkt {
-        Int(i32),
+    // Ordered structurally, ints before lists. The puzzle's order is `Pkt::compare`.
+    #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+    pub enum Pkt {
+        Int(BigInt),
         List(Vec<Pkt>),
     }
 
-    impl Pkt {        
-        fn parse(s: &str) -> Option<(Pkt, usize)> {
-            if s.is_empty() {
-                return None;
-            }
-            let num_pkt = Pkt::parse_num(s);
-            if num_pkt.is_some() {
-                return num_pkt;
-            }
-            Pkt::parse_list(s)
-        }
+    #[derive(Debug, PartialEq, Eq)]
+    pub struct ParseError {
+        pub pos: usize, // byte offset into the packet text
+        pub expected: &'static str,
+    }
 
-        fn parse_num(s: &str) -> Option<(Pkt, usize)> {
-            assert!(!s.is_empty());
-            match s.find(|c| [',', ']','['].contains(&c)) {
-                None => Some((s.parse::<i32>().unwrap().into(), s.chars().count())), // s contain only a number
-                Some(len) => match len {
-                    0 => None, // s starts with a ']', '[' or ','
-                    _ => Some((s[..len].parse::<i32>().unwrap().into(), len))
-                }
-            }
-        }
+    impl FromStr for Pkt {
+        type Err = ParseError;
 
-        fn parse_list(s: &str) -> Option<(Pkt, usize)> {
-            assert!(!s.is_empty());
-            assert_eq!(s.chars().next(), Some('['));
-            let mut len = 1; // '['
-            let mut vals = vec![];
+        // A single pass over the bytes, keeping the lists still open on a stack
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            let bytes = s.as_bytes();
+            let err = |pos, expected| Err(ParseError { pos, expected });
+            let mut open: Vec<Vec<Pkt>> = vec![];
+            let mut pos = 0;
             loop {
-                if s.chars().nth(len) == Some(']') {
-                    return Some((Self::List(vals), len + 1)); // include the ']'
-                } else if !vals.is_empty() { 
-                    len += 1; // skip ','
+                let mut value = match bytes.get(pos) {
+                    Some(b'[') if bytes.get(pos + 1) == Some(&b']') => {
+                        pos += 2;
+                        Pkt::List(vec![])
+                    }
+                    Some(b'[') => {
+                        pos += 1;
+                        open.push(vec![]);
+                        continue;
+                    }
+                    Some(b'0'..=b'9' | b'-') => {
+                        let start = pos;
+                        if bytes[pos] == b'-' {
+                            pos += 1;
+                        }
+                        let digits = pos;
+                        while bytes.get(pos).is_some_and(u8::is_ascii_digit) {
+                            pos += 1;
+                        }
+                        if pos == digits {
+                            return err(pos, "a digit");
+                        }
+                        Pkt::Int(s[start..pos].parse().unwrap())
+                    }
+                    _ => return err(pos, "'[' or an integer"),
+                };
+                // a value is complete, close every list that ends right after it
+                loop {
+                    let Some(list) = open.last_mut() else {
+                        return if pos == bytes.len() {
+                            Ok(value)
+                        } else {
+                            err(pos, "end of packet")
+                        };
+                    };
+                    list.push(value);
+                    match bytes.get(pos) {
+                        Some(b',') => {
+                            pos += 1;
+                            break;
+                        }
+                        Some(b']') => {
+                            pos += 1;
+                            value = Pkt::List(open.pop().unwrap());
+                        }
+                        _ => return err(pos, "',' or ']'"),
+                    }
                 }
-                match Pkt::parse(&s[len..]) {
-                    Some((pkt, pkt_len)) => {
-                        vals.push(pkt);
-                        len += pkt_len;
-                    },
-                    None => unreachable!(),
-                 }
             }
         }
     }
 
-    impl From<&str> for Pkt {
-        fn from(s: &str) -> Self {
-            match Pkt::parse(s) {
-                Some((p, _)) => p,
-                None => unreachable!(),
+    impl fmt::Display for Pkt {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Pkt::Int(v) => write!(f, "{v}"),
+                Pkt::List(vals) => {
+                    write!(f, "[")?;
+                    for (i, pkt) in vals.iter().enumerate() {
+                        if i > 0 {
+                            write!(f, ",")?;
+                        }
+                        write!(f, "{pkt}")?;
+                    }
+                    write!(f, "]")
+                }
             }
         }
     }
 
     impl From<i32> for Pkt {
         fn from(v: i32) -> Self {
-            Self::Int(v)
+            Self::Int(v.into())
         }
     }
 
-    impl PartialOrd for Pkt {
-        fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-            use Pkt::{Int, List};
-            let res = match (self, other) {
-                (Int(l), Int(r)) => Some(l.cmp(r)),
-                (Int(_), List(_)) => List(vec![self.clone()]).partial_cmp(other),
-                (List(_), Int(_)) => self.partial_cmp(&List(vec![other.clone()])),
-                (List(l), List(r)) => {
-                    for (lv, rv) in l.iter().zip(r.iter()) {
-                        match lv.partial_cmp(rv) {
-                            Some(std::cmp::Ordering::Equal) => {
-                                continue;
-                            },
-                            difference => {
-                                return difference;
-                            }
-                        };
-                    }
-                    Some(l.len().cmp(&r.len()))
-                }
-            };
-            res
+    #[cfg(test)]
+    mod tests {
+        use num_bigint::BigInt;
+        use proptest::prelude::*;
+
+        use std::cmp::Ordering::{Equal, Greater, Less};
+
+        use super::explain::{Promotion, Rule, Side};
+        use super::{InputError, ParseError, Pkt};
+
+        const EXAMPLE: &str = "\
+[1,1,3,1,1]
+[1,1,5,1,1]
+
+[[1],[2,3,4]]
+[[1],4]
+
+[9]
+[[8,7,6]]
+
+[[4,4],4,4]
+[[4,4],4,4,4]
+
+[7,7,7,7]
+[7,7,7]
+
+[]
+[3]
+
+[[[]]]
+[[]]
+
+[1,[2,[3,[4,[5,6,7]]]],8,9]
+[1,[2,[3,[4,[5,6,0]]]],8,9]
+";
+
+        #[test]
+        fn example_part1() {
+            assert_eq!(13, super::part1(EXAMPLE));
         }
-    }
 
-    impl PartialEq for Pkt {
-        fn eq(&self, other: &Self) -> bool {
-            match (self, other) {
-                (Pkt::Int(l), Pkt::Int(r)) => l == r,
-                (Pkt::Int(_), Pkt::List(_)) => false,
-                (Pkt::List(_), Pkt::Int(_)) => false,
-                (Pkt::List(l), Pkt::List(r)) => {
-                    l.len() == r.len() && l.iter().zip(r.iter()).all(|(pkt1, pkt2)| pkt1 == pkt2)
-                }
-            }
+        #[test]
+        fn example_part2() {
+            assert_eq!(140, super::part2(EXAMPLE));
         }
-    }
 
-    #[cfg(test)]
-    mod tests {
         #[test]
         fn real_part1() {
-            assert_eq!(5555, super::part1());
+            assert_eq!(5555, super::part1(crate::INPUT));
         }
 
         #[test]
         fn real_part2() {
-            assert_eq!(22852, super::part2());
+            assert_eq!(22852, super::part2(crate::INPUT));
+        }
+
+        #[test]
+        fn parse() {
+            use Pkt::{Int, List};
+            assert_eq!(Ok(Int(7.into())), "7".parse());
+            assert_eq!(Ok(List(vec![])), "[]".parse());
+            assert_eq!(
+                Ok(List(vec![1.into(), List(vec![List(vec![])]), (-2).into()])),
+                "[1,[[]],-2]".parse()
+            );
+        }
+
+        #[test]
+        fn parse_errors() {
+            let err = |pos, expected| Err::<Pkt, _>(ParseError { pos, expected });
+            assert_eq!(err(0, "'[' or an integer"), "".parse());
+            assert_eq!(err(4, "'[' or an integer"), "[1,[,2]]".parse());
+            assert_eq!(err(3, "',' or ']'"), "[[1".parse());
+            assert_eq!(err(2, "',' or ']'"), "[1 ,2]".parse());
+            assert_eq!(err(2, "end of packet"), "[]]".parse());
+            assert_eq!(err(2, "a digit"), "[-]".parse());
+            assert_eq!(err(3, "'[' or an integer"), "[1,]".parse());
+        }
+
+        #[test]
+        fn big_integers() {
+            let big = "123456789012345678901234567890";
+            let pkt: Pkt = format!("[{big},[-{big}]]").parse().unwrap();
+            assert_eq!(format!("[{big},[-{big}]]"), pkt.to_string());
+            let bigger: Pkt = format!("[{big}1]").parse().unwrap();
+            assert!(pkt.compare(&bigger).is_lt());
+        }
+
+        #[test]
+        fn deep_nesting() {
+            let deep = "[".repeat(10_000) + &"]".repeat(10_000);
+            assert!(deep.parse::<Pkt>().is_ok());
+        }
+
+        #[test]
+        fn display_real() {
+            for line in crate::INPUT.lines().filter(|l| !l.is_empty()) {
+                assert_eq!(line, line.parse::<Pkt>().unwrap().to_string());
+            }
+        }
+
+        fn any_pkt() -> impl Strategy<Value = Pkt> {
+            let int = prop_oneof![
+                any::<i64>().prop_map(|v| Pkt::Int(v.into())),
+                "-?[1-9][0-9]{0,40}".prop_map(|s| Pkt::Int(s.parse::<BigInt>().unwrap())),
+            ];
+            int.prop_recursive(8, 64, 6, |inner| {
+                prop::collection::vec(inner, 0..6).prop_map(Pkt::List)
+            })
+        }
+
+        proptest! {
+            #[test]
+            fn display_round_trip(pkt in any_pkt()) {
+                prop_assert_eq!(Ok(pkt.clone()), pkt.to_string().parse::<Pkt>());
+            }
+        }
+
+        #[test]
+        fn orders() {
+            let pkt = |s: &str| s.parse::<Pkt>().unwrap();
+            let (int, list) = (pkt("1"), pkt("[1]"));
+            // the same in the puzzle's order, but not the same packet
+            assert_eq!(Equal, int.compare(&list));
+            assert_ne!(int, list);
+            assert_eq!(Less, int.cmp(&list));
+            // so sorting, sets and dedup all agree with ==
+            let mut packets = vec![list.clone(), int.clone(), list.clone()];
+            packets.sort();
+            packets.dedup();
+            assert_eq!(vec![int.clone(), list.clone()], packets);
+            assert_eq!(2, std::collections::BTreeSet::from([int, list]).len());
+            assert_eq!(Less, pkt("[9]").cmp(&pkt("[[8,7,6]]")));
+            assert_eq!(Greater, pkt("[9]").compare(&pkt("[[8,7,6]]")));
+        }
+
+        #[test]
+        fn explain() {
+            let pkt = |s: &str| s.parse::<Pkt>().unwrap();
+            let (l, r) = (pkt("[1,1,3,1,1]"), pkt("[1,1,5,1,1]"));
+            let why = l.explain(&r);
+            assert_eq!((Less, vec![2]), (why.ordering, why.path.clone()));
+            assert_eq!(Rule::Ints(&3.into(), &5.into()), why.rule);
+            assert!(why.promotions.is_empty());
+
+            let (l, r) = (pkt("[[1],[2,3,4]]"), pkt("[[1],4]"));
+            let why = l.explain(&r);
+            assert_eq!((Less, vec![1, 0]), (why.ordering, why.path.clone()));
+            let promoted = Promotion {
+                path: vec![1],
+                side: Side::Right,
+            };
+            assert_eq!(vec![promoted], why.promotions);
+            assert_eq!(
+                "right order, 2 vs 4 at [1, 0], promoted right to a list at [1]",
+                why.to_string()
+            );
+
+            let (l, r) = (pkt("[9]"), pkt("[[8,7,6]]"));
+            let why = l.explain(&r);
+            assert_eq!((Greater, vec![0, 0]), (why.ordering, why.path.clone()));
+            assert_eq!(Side::Left, why.promotions[0].side);
+
+            let (l, r) = (pkt("[[4,4],4,4]"), pkt("[[4,4],4,4,4]"));
+            let why = l.explain(&r);
+            assert_eq!((Less, Rule::Lengths(3, 4)), (why.ordering, why.rule));
+            assert!(why.path.is_empty());
+
+            let (l, r) = (pkt("[[[]]]"), pkt("[[]]"));
+            let why = l.explain(&r);
+            assert_eq!((Greater, vec![0]), (why.ordering, why.path.clone()));
+            assert_eq!("wrong order, right ran out of items (1 vs 0) at [0]", why.to_string());
+
+            // promotions that compared equal still show, though the packets differ
+            let (l, r) = (pkt("[[1],2]"), pkt("[1,2]"));
+            let why = l.explain(&r);
+            assert_eq!((Equal, Rule::Identical), (why.ordering, why.rule));
+            assert_eq!(1, why.promotions.len());
+            assert_ne!(l, r);
+        }
+
+        #[test]
+        fn report() {
+            let args = |a: &[&str]| a.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+            assert_eq!(None, super::report(EXAMPLE, &[]));
+            assert_eq!(None, super::report(EXAMPLE, &args(&["explain", "some"])));
+            let wrong = super::report(EXAMPLE, &args(&["explain"])).unwrap();
+            let pairs: Vec<&str> = wrong.lines().map(|l| l.split(':').next().unwrap()).collect();
+            assert_eq!(vec!["pair 3", "pair 5", "pair 7", "pair 8"], pairs);
+            let first = "pair 3: wrong order, 9 vs 8 at [0, 0], promoted left to a list at [0]";
+            assert_eq!(Some(first), wrong.lines().next());
+            let all = super::report(EXAMPLE, &args(&["explain", "all"])).unwrap();
+            assert_eq!(8, all.lines().count());
+        }
+
+        #[test]
+        fn blank_lines() {
+            let pairs = |input| super::parse_pairs(input).map(|p| p.len());
+            assert_eq!(Ok(2), pairs("[1]\n[2]\n\n[3]\n[4]"));
+            assert_eq!(Ok(2), pairs("\n\n[1]\n[2]\n\n\n[3]\n[4]\n\n"));
+            assert_eq!(Ok(2), pairs("[1]\n[2]\n[3]\n[4]\n"));
+            assert_eq!(Ok(1), pairs("[1]\r\n[2]\r\n\r\n"));
+            assert_eq!(Ok(0), pairs(""));
+            assert_eq!(Err(InputError::Unpaired { line: 1 }), pairs("[1]\n\n[2]\n[3]"));
+            assert_eq!(Err(InputError::Unpaired { line: 3 }), pairs("[1]\n[2]\n[3]\n\n"));
+            let err = ParseError {
+                pos: 2,
+                expected: "',' or ']'",
+            };
+            assert_eq!(Err(InputError::Packet { line: 2, err }), pairs("[1]\n[2}"));
+        }
+
+        #[test]
+        fn dividers() {
+            let packets = super::collect_packets(EXAMPLE).unwrap();
+            let pkt = |s: &str| s.parse::<Pkt>().unwrap();
+            assert_eq!(140, super::decoder_key(&packets, &[pkt("[[2]]"), pkt("[[6]]")]));
+            // [[6]] alone sorts after 12 of the 16 packets
+            assert_eq!(13, super::decoder_key(&packets, &[pkt("[[6]]")]));
+            assert_eq!(1, super::decoder_key(&packets, &[pkt("[]")]));
+            let three = [pkt("[[2]]"), pkt("[[6]]"), pkt("[10]")];
+            assert_eq!(10 * 14 * 19, super::decoder_key(&packets, &three));
         }
     }
 }
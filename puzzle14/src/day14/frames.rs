@@ -0,0 +1,309 @@
+// Snapshots of the cave after every grain, and the sinks that show or save them.
+//
+// The parts take any `FrameSink`. `()` is the sink that does nothing, and everything it's fed
+// compiles away.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::gif::GifEncoder;
+use super::{Cave, Coord, DropResult, Tile};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pixel {
+    Air,
+    Rock,
+    Sand,
+    // The path of the grain that just dropped
+    Falling,
+    Source,
+}
+
+impl Pixel {
+    pub const ALL: [Pixel; 5] = [
+        Pixel::Air,
+        Pixel::Rock,
+        Pixel::Sand,
+        Pixel::Falling,
+        Pixel::Source,
+    ];
+
+    pub fn ascii(self) -> char {
+        match self {
+            Pixel::Air => '.',
+            Pixel::Rock => '#',
+            Pixel::Sand => 'o',
+            Pixel::Falling => '~',
+            Pixel::Source => '+',
+        }
+    }
+
+    pub fn rgb(self) -> [u8; 3] {
+        match self {
+            Pixel::Air => [16, 16, 24],
+            Pixel::Rock => [110, 110, 110],
+            Pixel::Sand => [220, 190, 90],
+            Pixel::Falling => [255, 80, 40],
+            Pixel::Source => [80, 200, 255],
+        }
+    }
+
+    fn gray(self) -> u8 {
+        match self {
+            Pixel::Air => 0,
+            Pixel::Rock => 100,
+            Pixel::Sand => 180,
+            Pixel::Falling => 230,
+            Pixel::Source => 255,
+        }
+    }
+}
+
+// The part of the cave to show, in cave coordinates
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Viewport {
+    pub left: i32,
+    pub top: i32,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Viewport {
+    // Everything placed so far, plus the widest pile any source could still build on the floor
+    pub fn fit(cave: &Cave) -> Self {
+        let depth = cave.depth as i32;
+        let stored = cave.minx..cave.minx + cave.columns.len() as i32;
+        let piles = cave
+            .sources
+            .iter()
+            .map(|s| s.at.x - depth..s.at.x + depth + 1);
+        let (left, right) = piles
+            .chain([stored])
+            .filter(|r| !r.is_empty())
+            .fold((i32::MAX, i32::MIN), |(l, r), xs| {
+                (l.min(xs.start), r.max(xs.end))
+            });
+        Self {
+            left,
+            top: 0,
+            width: (right - left) as usize,
+            height: depth as usize + 1,
+        }
+    }
+}
+
+pub struct Frame<'a> {
+    cave: &'a Cave,
+    fall: &'a [Coord],
+    rest: Option<Coord>,
+}
+
+impl<'a> Frame<'a> {
+    // After a grain dropped from source `id`
+    pub fn new(cave: &'a Cave, id: usize, res: &DropResult) -> Self {
+        Self {
+            cave,
+            fall: &cave.sources[id].path,
+            rest: match res {
+                DropResult::Rest(c) => Some(*c),
+                _ => None,
+            },
+        }
+    }
+
+    // Row by row
+    pub fn render(&self, view: &Viewport) -> Vec<Pixel> {
+        let at = |c: Coord| {
+            let (x, y) = (c.x - view.left, c.y - view.top);
+            let inside =
+                (0..view.width as i32).contains(&x) && (0..view.height as i32).contains(&y);
+            inside.then(|| y as usize * view.width + x as usize)
+        };
+        let mut pixels = Vec::with_capacity(view.width * view.height);
+        for y in view.top..view.top + view.height as i32 {
+            for x in view.left..view.left + view.width as i32 {
+                pixels.push(match self.cave.tile(Coord { x, y }) {
+                    Tile::Air => Pixel::Air,
+                    Tile::Rock => Pixel::Rock,
+                    Tile::Sand => Pixel::Sand,
+                });
+            }
+        }
+        let fall = self.fall.iter().filter(|&&c| Some(c) != self.rest);
+        for i in fall.filter_map(|&c| at(c)) {
+            pixels[i] = Pixel::Falling;
+        }
+        for i in self.cave.sources.iter().filter_map(|s| at(s.at)) {
+            pixels[i] = Pixel::Source;
+        }
+        pixels
+    }
+}
+
+pub trait FrameSink {
+    fn frame(&mut self, frame: &Frame) -> io::Result<()>;
+
+    fn finish(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl FrameSink for () {
+    #[inline(always)]
+    fn frame(&mut self, _: &Frame) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn ascii(pixels: &[Pixel], width: usize) -> String {
+    pixels
+        .chunks(width)
+        .flat_map(|row| row.iter().map(|p| p.ascii()).chain(['\n']))
+        .collect()
+}
+
+// Redraws the cave in place, at most `fps` times a second
+pub struct Terminal<W: Write> {
+    out: W,
+    view: Viewport,
+    frame_time: Duration,
+    last: Option<Instant>,
+}
+
+impl<W: Write> Terminal<W> {
+    pub fn new(out: W, view: Viewport, fps: u32) -> Self {
+        Self {
+            out,
+            view,
+            frame_time: Duration::from_secs(1) / fps.max(1),
+            last: None,
+        }
+    }
+}
+
+impl<W: Write> FrameSink for Terminal<W> {
+    fn frame(&mut self, frame: &Frame) -> io::Result<()> {
+        match self.last {
+            // clear the screen before the first frame, go back to the top left before the rest
+            None => write!(self.out, "\x1b[2J\x1b[H")?,
+            Some(last) => {
+                thread::sleep(self.frame_time.saturating_sub(last.elapsed()));
+                write!(self.out, "\x1b[H")?;
+            }
+        }
+        let pixels = frame.render(&self.view);
+        self.out
+            .write_all(ascii(&pixels, self.view.width).as_bytes())?;
+        self.out.flush()?;
+        self.last = Some(Instant::now());
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pnm {
+    // color
+    Ppm,
+    // grayscale
+    Pgm,
+}
+
+// Writes every frame to its own numbered image, with each tile `scale` pixels wide
+pub struct ImageSequence {
+    dir: PathBuf,
+    format: Pnm,
+    view: Viewport,
+    scale: usize,
+    count: usize,
+}
+
+impl ImageSequence {
+    pub fn new(dir: impl Into<PathBuf>, format: Pnm, view: Viewport, scale: usize) -> Self {
+        Self {
+            dir: dir.into(),
+            format,
+            view,
+            scale,
+            count: 0,
+        }
+    }
+}
+
+impl FrameSink for ImageSequence {
+    fn frame(&mut self, frame: &Frame) -> io::Result<()> {
+        let ext = match self.format {
+            Pnm::Ppm => "ppm",
+            Pnm::Pgm => "pgm",
+        };
+        let path = self.dir.join(format!("frame{:06}.{ext}", self.count));
+        let mut out = BufWriter::new(File::create(path)?);
+        let (width, height) = (self.view.width * self.scale, self.view.height * self.scale);
+        let magic = match self.format {
+            Pnm::Ppm => "P6",
+            Pnm::Pgm => "P5",
+        };
+        write!(out, "{magic}\n{width} {height}\n255\n")?;
+        for p in scale(&frame.render(&self.view), self.view.width, self.scale) {
+            match self.format {
+                Pnm::Ppm => out.write_all(&p.rgb())?,
+                Pnm::Pgm => out.write_all(&[p.gray()])?,
+            }
+        }
+        self.count += 1;
+        out.flush()
+    }
+}
+
+// Each pixel becomes a `scale` by `scale` square
+fn scale(pixels: &[Pixel], width: usize, scale: usize) -> impl Iterator<Item = Pixel> + '_ {
+    pixels.chunks(width).flat_map(move |row| {
+        let wide = move || row.iter().flat_map(move |&p| std::iter::repeat_n(p, scale));
+        (0..scale).flat_map(move |_| wide())
+    })
+}
+
+// An animated GIF, `delay` hundredths of a second per frame
+pub struct Gif<W: Write> {
+    encoder: Option<GifEncoder<W>>,
+    view: Viewport,
+    scale: usize,
+    delay: u16,
+}
+
+impl<W: Write> Gif<W> {
+    pub fn new(out: W, view: Viewport, scale: usize, delay: u16) -> io::Result<Self> {
+        let (width, height) = (view.width * scale, view.height * scale);
+        let too_big = || io::Error::new(io::ErrorKind::InvalidInput, "a GIF is at most 65535 wide");
+        let width = u16::try_from(width).map_err(|_| too_big())?;
+        let height = u16::try_from(height).map_err(|_| too_big())?;
+        let palette = Pixel::ALL.map(Pixel::rgb);
+        Ok(Self {
+            encoder: Some(GifEncoder::new(out, width, height, &palette)?),
+            view,
+            scale,
+            delay,
+        })
+    }
+}
+
+impl<W: Write> FrameSink for Gif<W> {
+    fn frame(&mut self, frame: &Frame) -> io::Result<()> {
+        let finished = || io::Error::other("the GIF is finished");
+        let encoder = self.encoder.as_mut().ok_or_else(finished)?;
+        let pixels = frame.render(&self.view);
+        let indices: Vec<u8> = scale(&pixels, self.view.width, self.scale)
+            .map(|p| Pixel::ALL.iter().position(|&q| q == p).unwrap() as u8)
+            .collect();
+        encoder.add_frame(&indices, self.delay)
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        match self.encoder.take() {
+            Some(encoder) => encoder.finish().map(|_| ()),
+            None => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,136 @@
+// A minimal animated GIF89a encoder: one global palette, LZW compressed frames, looping forever.
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+const MAX_CODES: u16 = 4096;
+
+pub struct GifEncoder<W: Write> {
+    out: W,
+    width: u16,
+    height: u16,
+    // log2 of the palette size, padded to at least 4 colors
+    palette_bits: u8,
+}
+
+impl<W: Write> GifEncoder<W> {
+    pub fn new(mut out: W, width: u16, height: u16, palette: &[[u8; 3]]) -> io::Result<Self> {
+        assert!(!palette.is_empty() && palette.len() <= 256);
+        let palette_bits = (palette.len().next_power_of_two().trailing_zeros() as u8).max(2);
+
+        out.write_all(b"GIF89a")?;
+        out.write_all(&width.to_le_bytes())?;
+        out.write_all(&height.to_le_bytes())?;
+        // global color table, 8 bit color resolution, table size
+        out.write_all(&[0x80 | 0x70 | (palette_bits - 1), 0, 0])?;
+        for i in 0..1 << palette_bits {
+            out.write_all(palette.get(i).unwrap_or(&[0, 0, 0]))?;
+        }
+        // loop forever
+        out.write_all(b"\x21\xff\x0bNETSCAPE2.0\x03\x01\x00\x00\x00")?;
+        Ok(Self {
+            out,
+            width,
+            height,
+            palette_bits,
+        })
+    }
+
+    // `pixels` are palette indices, row by row. `delay` is in hundredths of a second.
+    pub fn add_frame(&mut self, pixels: &[u8], delay: u16) -> io::Result<()> {
+        assert_eq!(self.width as usize * self.height as usize, pixels.len());
+        // graphic control extension, for the delay
+        self.out.write_all(&[0x21, 0xf9, 4, 0])?;
+        self.out.write_all(&delay.to_le_bytes())?;
+        self.out.write_all(&[0, 0])?;
+        // image descriptor, covering the whole screen
+        self.out.write_all(&[0x2c, 0, 0, 0, 0])?;
+        self.out.write_all(&self.width.to_le_bytes())?;
+        self.out.write_all(&self.height.to_le_bytes())?;
+        self.out.write_all(&[0])?;
+
+        self.out.write_all(&[self.palette_bits])?;
+        for block in lzw(pixels, self.palette_bits).chunks(255) {
+            self.out.write_all(&[block.len() as u8])?;
+            self.out.write_all(block)?;
+        }
+        self.out.write_all(&[0])
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        self.out.write_all(&[0x3b])?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+// Codes are packed least significant bit first
+struct Bits {
+    bytes: Vec<u8>,
+    acc: u32,
+    len: u8,
+}
+
+impl Bits {
+    fn push(&mut self, code: u16, size: u8) {
+        self.acc |= (code as u32) << self.len;
+        self.len += size;
+        while self.len >= 8 {
+            self.bytes.push(self.acc as u8);
+            self.acc >>= 8;
+            self.len -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.len > 0 {
+            self.bytes.push(self.acc as u8);
+        }
+        self.bytes
+    }
+}
+
+fn lzw(pixels: &[u8], min_size: u8) -> Vec<u8> {
+    let clear = 1u16 << min_size;
+    let end = clear + 1;
+    let mut bits = Bits {
+        bytes: vec![],
+        acc: 0,
+        len: 0,
+    };
+    let mut size = min_size + 1;
+    let mut next = end + 1;
+    let mut codes: HashMap<(u16, u8), u16> = HashMap::new();
+
+    bits.push(clear, size);
+    let Some((&first, rest)) = pixels.split_first() else {
+        bits.push(end, size);
+        return bits.finish();
+    };
+    let mut cur = first as u16;
+    for &pixel in rest {
+        if let Some(&code) = codes.get(&(cur, pixel)) {
+            cur = code;
+            continue;
+        }
+        bits.push(cur, size);
+        if next == MAX_CODES {
+            // the table is full, start over
+            bits.push(clear, size);
+            codes.clear();
+            size = min_size + 1;
+            next = end + 1;
+        } else {
+            codes.insert((cur, pixel), next);
+            next += 1;
+            // the decoder adds each code one step later, and widens once it fills the width
+            if next > 1 << size && size < 12 {
+                size += 1;
+            }
+        }
+        cur = pixel as u16;
+    }
+    bits.push(cur, size);
+    bits.push(end, size);
+    bits.finish()
+}
@@ -0,0 +1,138 @@
+// Sand sources, when they emit and what became of their grains.
+
+use super::{Cave, Coord, DropResult};
+
+// Emits a grain on ticks `start`, `start + every`, ... until `limit` grains were emitted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Schedule {
+    pub start: u64,
+    pub every: u64,
+    pub limit: Option<u64>,
+}
+
+impl Schedule {
+    pub const ALWAYS: Schedule = Schedule {
+        start: 0,
+        every: 1,
+        limit: None,
+    };
+
+    fn due(&self, tick: u64, emitted: u64) -> bool {
+        tick >= self.start
+            && (tick - self.start).is_multiple_of(self.every)
+            && self.limit.is_none_or(|limit| emitted < limit)
+    }
+}
+
+// A grain, by the source it came from and its 1-based number there
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Grain {
+    pub source: usize,
+    pub number: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Clog {
+    pub tick: u64,
+    // The grain that came to rest on the source. None if it was covered by rock, or by sand
+    // that was there before the source.
+    pub grain: Option<Grain>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SourceStats {
+    pub emitted: u64,
+    pub resting: u64,
+    pub fallen: u64,
+    pub clogged: Option<Clog>,
+}
+
+pub struct Source {
+    pub at: Coord,
+    pub schedule: Schedule,
+    // The previous grain's fall from the source, cut short whenever a tile along it changes.
+    // The next grain starts from its end rather than from the source.
+    pub path: Vec<Coord>,
+    pub stats: SourceStats,
+}
+
+impl Source {
+    pub fn new(at: Coord, schedule: Schedule) -> Self {
+        Self {
+            at,
+            schedule,
+            path: vec![],
+            stats: SourceStats::default(),
+        }
+    }
+}
+
+impl Cave {
+    // The new source's id, None if it's above the cave
+    pub fn add_source(&mut self, at: Coord, schedule: Schedule) -> Option<usize> {
+        assert!(schedule.every > 0, "a schedule must emit every 1 tick or more");
+        if at.y < 0 {
+            return None;
+        }
+        self.sources.push(Source::new(at, schedule));
+        Some(self.sources.len() - 1)
+    }
+
+    pub fn stats(&self, id: usize) -> &SourceStats {
+        &self.sources[id].stats
+    }
+
+    // Drops a grain from every source that's due, in the order they were added
+    pub fn tick(&mut self, mut on_drop: impl FnMut(usize, &DropResult)) {
+        for id in 0..self.sources.len() {
+            let source = &self.sources[id];
+            if source.schedule.due(self.tick, source.stats.emitted) {
+                let res = self.drop_from(id);
+                on_drop(id, &res);
+            }
+        }
+        self.tick += 1;
+    }
+
+    pub(super) fn rested(&mut self, id: usize, at: Coord) {
+        let grain = Grain {
+            source: id,
+            number: self.sources[id].stats.emitted,
+        };
+        self.sources[id].stats.resting += 1;
+        self.block(at, Some(id));
+        for source in self.sources.iter_mut().filter(|s| s.at == at) {
+            source.stats.clogged.get_or_insert(Clog {
+                tick: self.tick,
+                grain: Some(grain),
+            });
+        }
+    }
+
+    // A tile was filled, so falls through it end right above it. The grain's own source
+    // (`filler`) already dropped it from its fall.
+    pub(super) fn block(&mut self, filled: Coord, filler: Option<usize>) {
+        for (id, source) in self.sources.iter_mut().enumerate() {
+            if Some(id) == filler {
+                continue;
+            }
+            if let Some(i) = source.path.iter().position(|&c| c == filled) {
+                source.path.truncate(i);
+            }
+        }
+    }
+
+    // Tiles that became free can change where a grain falls next, so each fall is cut right
+    // after the first tile above it
+    pub(super) fn reopen(&mut self, freed: Coord) {
+        for source in &mut self.sources {
+            let above = source
+                .path
+                .iter()
+                .position(|c| c.y + 1 == freed.y && c.x.abs_diff(freed.x) <= 1);
+            if let Some(i) = above {
+                source.path.truncate(i + 1);
+            }
+        }
+    }
+}
@@ -1,4 +1,23 @@
 fn main() {
+    // `puzzle14 sources abyss|floor [from=x,y] [source=x,y[,start,every[,limit]]]...
+    // [rock=T:path]... [unrock=T:path]...` pours sand from more sources instead, adding or
+    // removing rock along a path like `497,2->503,2` on tick T, and tells what became of every
+    // source's grains
+    //
+    // `puzzle14 frames part1|part2 terminal [fps]|ppm|pgm <dir> [scale]|gif <file> [scale] [delay]`
+    // shows the sand of a part falling instead, redrawn in the terminal, as numbered images or as
+    // an animated GIF with `delay` hundredths of a second per grain
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(report) = day14::report(INPUT, &args) {
+        print!("{report}");
+        return;
+    }
+    if let Some(count) = day14::animate(INPUT, &args) {
+        let count = count.unwrap_or_else(|e| panic!("can't draw the frames: {e}"));
+        println!("{count}");
+        return;
+    }
+
     for func in [day14::part1, day14::part2] {
         let start = std::time::Instant::now();
         let res = func(INPUT);
@@ -9,155 +28,523 @@ fn main() {
 }
 
 mod day14 {
+    use std::collections::VecDeque;
+    use std::fmt::{self, Write};
+    use std::fs::{self, File};
+    use std::io::{self, BufWriter};
+    use std::str::FromStr;
+
+    use frames::{Frame, FrameSink, Gif, ImageSequence, Pnm, Terminal, Viewport};
+    use sources::{Clog, Schedule, Source};
+
+    mod frames;
+    mod gif;
+    mod sources;
+
+    const SOURCE: Coord = Coord { x: 500, y: 0 };
+
     pub fn part1(input: &str) -> u64 {
-        let mut cave = Cave::from(input);
-        cave.print();
+        part1_with(input, &mut ()).unwrap()
+    }
+
+    pub fn part2(input: &str) -> u64 {
+        part2_with(input, &mut ()).unwrap()
+    }
+
+    // Runs until every source is covered, out of grains or losing them to the abyss, and there
+    // are no rock edits left to make
+    pub fn report(input: &str, args: &[String]) -> Option<String> {
+        let (cmd, rest) = args.split_first()?;
+        if cmd != "sources" {
+            return None;
+        }
+        let (bottom, rest) = rest.split_first()?;
+        let bottom = match bottom.as_str() {
+            "abyss" => Bottom::Abyss,
+            "floor" => Bottom::Floor,
+            _ => return None,
+        };
+        let mut cave = Cave::new(input, bottom).unwrap_or_else(|e| panic!("{e}"));
+        let mut edits = vec![];
+        for arg in rest {
+            let (key, value) = arg.split_once('=')?;
+            match key {
+                "from" => cave = cave.with_source(value.parse().ok()?)?,
+                "source" => {
+                    let (at, schedule) = parse_source(value)?;
+                    cave.add_source(at, schedule)?;
+                }
+                "rock" | "unrock" => {
+                    let (tick, path) = value.split_once(':')?;
+                    parse_path(path).ok()?;
+                    edits.push((tick.parse::<u64>().ok()?, key == "rock", path));
+                }
+                _ => return None,
+            }
+        }
+
+        let mut out = String::new();
+        // whether each source's last grain fell into the abyss
+        let mut fell = vec![false; cave.sources.len()];
+        loop {
+            let finished = cave.sources.iter().zip(&fell).all(|(s, &fell)| {
+                let limit = s.schedule.limit;
+                fell || cave.tile(s.at) != Tile::Air
+                    || limit.is_some_and(|limit| s.stats.emitted >= limit)
+            });
+            if finished && edits.iter().all(|&(tick, ..)| tick < cave.tick) {
+                break;
+            }
+            let now = cave.tick;
+            for &(tick, add, path) in edits.iter().filter(|e| e.0 == now) {
+                let res = match add {
+                    true => cave.add_rock(path),
+                    false => cave.remove_rock(path).map_err(RockError::from),
+                };
+                let verb = if add { "add" } else { "remove" };
+                match res {
+                    Ok(()) => writeln!(out, "tick {tick}: {verb} rock {path}").unwrap(),
+                    Err(e) => writeln!(out, "tick {tick}: can't {verb} rock {path}: {e}").unwrap(),
+                }
+            }
+            cave.tick(|id, res| fell[id] = *res == DropResult::Abyss);
+        }
+        for (id, source) in cave.sources.iter().enumerate() {
+            let stats = cave.stats(id);
+            write!(
+                out,
+                "source {id} at {}: emitted {}, resting {}, fallen {}",
+                source.at, stats.emitted, stats.resting, stats.fallen
+            )
+            .unwrap();
+            match stats.clogged {
+                None => writeln!(out, ", not clogged"),
+                Some(Clog { tick, grain: None }) => writeln!(out, ", clogged on tick {tick}"),
+                Some(Clog {
+                    tick,
+                    grain: Some(grain),
+                }) => writeln!(
+                    out,
+                    ", clogged on tick {tick} by grain {} of source {}",
+                    grain.number, grain.source
+                ),
+            }
+            .unwrap();
+        }
+        writeln!(out, "stopped after {} ticks", cave.tick).unwrap();
+        Some(out)
+    }
+
+    // "x,y", then optionally ",start,every" and ",limit" for its schedule
+    fn parse_source(s: &str) -> Option<(Coord, Schedule)> {
+        let mut fields = s.splitn(3, ',');
+        let x = fields.next()?.parse().ok()?;
+        let y = fields.next()?.parse().ok()?;
+        let schedule = match fields.next() {
+            None => Schedule::ALWAYS,
+            Some(rest) => {
+                let nums = rest
+                    .split(',')
+                    .map(|n| n.parse().ok())
+                    .collect::<Option<Vec<u64>>>()?;
+                let (start, every, limit) = match nums[..] {
+                    [start, every] => (start, every, None),
+                    [start, every, limit] => (start, every, Some(limit)),
+                    _ => return None,
+                };
+                if every == 0 {
+                    return None;
+                }
+                Schedule {
+                    start,
+                    every,
+                    limit,
+                }
+            }
+        };
+        Some((Coord { x, y }, schedule))
+    }
+
+    // Draws a part on the sink named in `args`, over the whole cave the sand could ever cover
+    pub fn animate(input: &str, args: &[String]) -> Option<io::Result<u64>> {
+        let (cmd, rest) = args.split_first()?;
+        if cmd != "frames" {
+            return None;
+        }
+        let (part, rest) = rest.split_first()?;
+        let bottom = match part.as_str() {
+            "part1" => Bottom::Abyss,
+            "part2" => Bottom::Floor,
+            _ => return None,
+        };
+        let cave = Cave::new(input, bottom).unwrap_or_else(|e| panic!("{e}"));
+        let view = Viewport::fit(&cave);
+        let (sink, rest) = rest.split_first()?;
+        let format = match sink.as_str() {
+            "ppm" => Some(Pnm::Ppm),
+            "pgm" => Some(Pnm::Pgm),
+            _ => None,
+        };
+        Some(match (sink.as_str(), rest) {
+            ("terminal", [] | [_]) => {
+                let fps = arg(rest, 0, 30)?;
+                let mut sink = Terminal::new(io::stdout().lock(), view, fps);
+                fill_with(input, bottom, &mut sink)
+            }
+            ("ppm" | "pgm", [dir] | [dir, _]) => {
+                let scale = arg(rest, 1, 2).filter(|&s| s > 0)?;
+                let mut sink = ImageSequence::new(dir, format?, view, scale);
+                fs::create_dir_all(dir).and_then(|()| fill_with(input, bottom, &mut sink))
+            }
+            ("gif", [file] | [file, _] | [file, _, _]) => {
+                let scale = arg(rest, 1, 2).filter(|&s| s > 0)?;
+                let delay = arg(rest, 2, 5)?;
+                File::create(file).and_then(|out| {
+                    let mut sink = Gif::new(BufWriter::new(out), view, scale, delay)?;
+                    fill_with(input, bottom, &mut sink)
+                })
+            }
+            _ => return None,
+        })
+    }
+
+    // The `i`th argument, or `default` if there are fewer
+    fn arg<T: FromStr>(args: &[String], i: usize, default: T) -> Option<T> {
+        args.get(i).map_or(Some(default), |a| a.parse().ok())
+    }
+
+    fn fill_with(input: &str, bottom: Bottom, sink: &mut impl FrameSink) -> io::Result<u64> {
+        match bottom {
+            Bottom::Abyss => part1_with(input, sink),
+            Bottom::Floor => part2_with(input, sink),
+        }
+    }
+
+    // Feeds `sink` a frame after every grain, the last one included
+    pub fn part1_with(input: &str, sink: &mut impl FrameSink) -> io::Result<u64> {
+        let mut cave = Cave::new(input, Bottom::Abyss).unwrap_or_else(|e| panic!("{e}"));
         let mut sand_count = 0;
         loop {
-            match cave.drop(500, 0) {
-                DropResult::Rest => sand_count += 1,
-                DropResult::Floor => break,
+            let res = cave.drop();
+            sink.frame(&Frame::new(&cave, 0, &res))?;
+            match res {
+                DropResult::Rest(_) => sand_count += 1,
+                DropResult::Abyss => break,
                 DropResult::Clogged => unreachable!(),
             }
-            cave.print();
         }
-        sand_count
+        sink.finish()?;
+        Ok(sand_count)
     }
 
-    pub fn part2(input: &str) -> u64 {
-        let mut cave = Cave::from(input);
-        cave.print();
+    pub fn part2_with(input: &str, sink: &mut impl FrameSink) -> io::Result<u64> {
+        let mut cave = Cave::new(input, Bottom::Floor).unwrap_or_else(|e| panic!("{e}"));
         let mut sand_count = 0;
         loop {
-            match cave.drop(500, 0) {
+            let res = cave.drop();
+            sink.frame(&Frame::new(&cave, 0, &res))?;
+            match res {
+                DropResult::Rest(_) => sand_count += 1,
                 DropResult::Clogged => break,
-                _ => sand_count += 1,
+                DropResult::Abyss => unreachable!(),
             }
-            cave.print();
         }
-        sand_count
+        sink.finish()?;
+        Ok(sand_count)
     }
 
-    enum DropResult {
-        Rest,
+    // What lies below the lowest rock
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Bottom {
+        // Nothing, sand falls out of the cave forever
+        Abyss,
+        // An endless floor two below the lowest rock
         Floor,
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    pub enum DropResult {
+        Rest(Coord),
+        Abyss,
+        // The source is covered, nothing more can drop
         Clogged,
     }
 
-    struct Cave {
-        grid: Vec<Vec<bool>>,
-        minx: usize,
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Tile {
+        Air,
+        Rock,
+        Sand,
+    }
+
+    pub struct Cave {
+        // Only columns that had something placed in them are stored, the rest are air. Each
+        // column is `depth` tiles, from y = 0 down to just above the floor.
+        columns: VecDeque<Vec<Tile>>,
+        minx: i32,
+        depth: usize,
+        bottom: Bottom,
+        sources: Vec<Source>,
+        tick: u64,
     }
 
     impl Cave {
-        fn drop(&mut self, x: usize, y: usize) -> DropResult {
-            const X_PRIO: [isize; 3] = [0, -1, 1];
-            let mut cur = Coord { x, y };
-            if !self.empty(&cur) {
-                return DropResult::Clogged;
+        pub fn new(input: &str, bottom: Bottom) -> Result<Self, ParseError> {
+            let mut cave = Self {
+                columns: VecDeque::new(),
+                minx: SOURCE.x,
+                depth: 2,
+                bottom,
+                sources: vec![],
+                tick: 0,
+            };
+            for l in input.lines() {
+                cave.place_rock(parse_path(l)?);
             }
-            'dropping: loop {
+            cave.add_source(SOURCE, Schedule::ALWAYS).unwrap();
+            Ok(cave)
+        }
+
+        // Moves the first source, None if that's above the cave
+        pub fn with_source(mut self, at: Coord) -> Option<Self> {
+            if at.y < 0 {
+                return None;
+            }
+            self.sources[0] = Source::new(at, self.sources[0].schedule);
+            Some(self)
+        }
+
+        // Adds rock along a path of "x,y -> x,y -> ..." vertices. Rock below the lowest one so
+        // far pushes the bottom (abyss or floor) down with it, unless sand already rests on the
+        // floor and would be left hanging.
+        pub fn add_rock(&mut self, path: &str) -> Result<(), RockError> {
+            let segments = parse_path(path)?;
+            let lowest = segments.iter().map(|(s, e)| s.y.max(e.y) as usize).max();
+            let on_floor = |col: &Vec<Tile>| col[self.depth - 1] == Tile::Sand;
+            if self.bottom == Bottom::Floor
+                && lowest.is_some_and(|y| y + 2 > self.depth)
+                && self.columns.iter().any(on_floor)
+            {
+                return Err(RockError::SandOnFloor);
+            }
+            self.place_rock(segments);
+            Ok(())
+        }
+
+        fn place_rock(&mut self, segments: Vec<(Coord, Coord)>) {
+            for (start, end) in segments {
+                let lowest = start.y.max(end.y) as usize;
+                if lowest + 2 > self.depth {
+                    // keep the bottom two below the lowest rock
+                    self.depth = lowest + 2;
+                    for col in &mut self.columns {
+                        col.resize(self.depth, Tile::Air);
+                    }
+                }
+                for c in segment(start, end) {
+                    self.set(c, Tile::Rock);
+                    self.block(c, None);
+                }
+            }
+        }
+
+        // Removes the rock along a path, leaving any sand in place
+        pub fn remove_rock(&mut self, path: &str) -> Result<(), ParseError> {
+            for (start, end) in parse_path(path)? {
+                for c in segment(start, end) {
+                    if self.tile(c) == Tile::Rock {
+                        self.set(c, Tile::Air);
+                        self.reopen(c);
+                    }
+                }
+            }
+            Ok(())
+        }
+
+        // Drops a grain from the first source
+        pub fn drop(&mut self) -> DropResult {
+            self.drop_from(0)
+        }
+
+        pub fn drop_from(&mut self, id: usize) -> DropResult {
+            const X_PRIO: [i32; 3] = [0, -1, 1];
+            let mut path = std::mem::take(&mut self.sources[id].path);
+            if path.is_empty() {
+                let at = self.sources[id].at;
+                if self.tile(at) != Tile::Air {
+                    let clog = Clog {
+                        tick: self.tick,
+                        grain: None,
+                    };
+                    self.sources[id].stats.clogged.get_or_insert(clog);
+                    return DropResult::Clogged;
+                }
+                path.push(at);
+            }
+            self.sources[id].stats.emitted += 1;
+            let mut cur = *path.last().unwrap();
+            let res = 'dropping: loop {
+                if self.bottom == Bottom::Abyss && cur.y as usize >= self.depth - 1 {
+                    // below the lowest rock
+                    self.sources[id].stats.fallen += 1;
+                    break DropResult::Abyss;
+                }
                 for adv in X_PRIO {
                     let next = Coord {
-                        x: (cur.x as isize + adv) as usize,
+                        x: cur.x + adv,
                         y: cur.y + 1,
                     };
-                    if self.empty(&next) {
+                    if self.tile(next) == Tile::Air {
+                        path.push(next);
                         cur = next;
                         continue 'dropping;
                     }
                 }
                 // no where to go
-                self.fill(&cur);
-                if cur.y == self.grid.len() - 2 {
-                    return DropResult::Floor;
-                }
-                return DropResult::Rest;
+                self.set(cur, Tile::Sand);
+                path.pop();
+                self.rested(id, cur);
+                break DropResult::Rest(cur);
+            };
+            self.sources[id].path = path;
+            res
+        }
+
+        pub fn tile(&self, c: Coord) -> Tile {
+            if c.y < 0 {
+                return Tile::Air;
             }
+            if c.y as usize >= self.depth {
+                return match self.bottom {
+                    Bottom::Floor => Tile::Rock,
+                    Bottom::Abyss => Tile::Air,
+                };
+            }
+            usize::try_from(c.x - self.minx)
+                .ok()
+                .and_then(|col| self.columns.get(col))
+                .map_or(Tile::Air, |col| col[c.y as usize])
         }
 
-        fn empty(&self, coord: &Coord) -> bool {
-            !self.grid[coord.y][coord.x - self.minx]
+        fn set(&mut self, c: Coord, tile: Tile) {
+            while c.x < self.minx || self.columns.is_empty() {
+                if self.columns.is_empty() {
+                    self.minx = c.x;
+                } else {
+                    self.minx -= 1;
+                }
+                self.columns.push_front(vec![Tile::Air; self.depth]);
+            }
+            while c.x >= self.minx + self.columns.len() as i32 {
+                self.columns.push_back(vec![Tile::Air; self.depth]);
+            }
+            self.columns[(c.x - self.minx) as usize][c.y as usize] = tile;
         }
+    }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Coord {
+        pub x: i32,
+        pub y: i32,
+    }
+
+    #[derive(Debug, PartialEq, Eq)]
+    pub struct ParseError {
+        pub vertex: String,
+    }
 
-        fn fill(&mut self, coord: &Coord) {
-            let actualx = coord.x - self.minx;
-            self.grid[coord.y][actualx] = true;
+    impl fmt::Display for Coord {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{},{}", self.x, self.y)
         }
+    }
 
-        fn print(&self) {
-            // For debugging - uncomment
-            // for (i, l) in self.grid.iter().enumerate() {
-            //     print!("{i:2} ");
-            //     for b in l {
-            //         if *b {
-            //             print!("#");
-            //         } else {
-            //             print!(".");
-            //         }
-            //     }
-            //     println!();
-            // }
-            // println!();
+    impl fmt::Display for ParseError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "bad rock vertex {:?}", self.vertex)
         }
     }
 
-    struct Coord {
-        x: usize,
-        y: usize,
+    #[derive(Debug, PartialEq, Eq)]
+    pub enum RockError {
+        Parse(ParseError),
+        // Moving the floor down would leave the sand on it floating
+        SandOnFloor,
     }
 
-    impl From<&str> for Coord {
-        fn from(s: &str) -> Self {
-            let mut parts = s.split(',').map(|ps| ps.parse::<usize>().unwrap());
-            Self {
-                x: parts.next().unwrap(),
-                y: parts.next().unwrap(),
+    impl fmt::Display for RockError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                RockError::Parse(e) => write!(f, "{e}"),
+                RockError::SandOnFloor => write!(f, "sand rests on the floor"),
             }
         }
     }
 
-    // cave parsing
-    impl From<&str> for Cave {
-        fn from(input: &str) -> Self {
-            use std::cmp::{max, min};
+    impl From<ParseError> for RockError {
+        fn from(e: ParseError) -> Self {
+            RockError::Parse(e)
+        }
+    }
 
-            let mut ranges: Vec<(Coord, Coord)> = vec![];
-            for l in input.lines() {
-                let vertices = l.split(" -> ");
-                let ends = vertices.clone().skip(1);
-                for (start, end) in vertices.zip(ends) {
-                    ranges.push((start.into(), end.into()));
-                }
-            }
-            let maxy = ranges.iter().map(|r| max(r.0.y, r.1.y)).max().unwrap();
-            let minx = 500 - maxy - 2;
-            let maxx = 500 + maxy + 2;
+    impl FromStr for Coord {
+        type Err = ParseError;
 
-            let mut cave = Self {
-                grid: vec![vec![false; maxx - minx + 1]; maxy + 2],
-                minx,
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            let err = || ParseError {
+                vertex: s.trim().to_string(),
             };
-            cave.grid.push(vec![true; maxx - minx + 1]);
-
-            for r in ranges {
-                let startx = min(r.0.x, r.1.x);
-                let endx = max(r.0.x, r.1.x);
-                let starty = min(r.0.y, r.1.y);
-                let endy = max(r.0.y, r.1.y);
-                for x in startx..=endx {
-                    for y in starty..=endy {
-                        cave.fill(&Coord { x, y });
-                    }
-                }
+            let (x, y) = s.trim().split_once(',').ok_or_else(err)?;
+            let coord = Self {
+                x: x.parse().map_err(|_| err())?,
+                y: y.parse().map_err(|_| err())?,
+            };
+            match coord.y >= 0 {
+                true => Ok(coord),
+                false => Err(err()),
             }
+        }
+    }
 
-            cave
+    fn parse_path(path: &str) -> Result<Vec<(Coord, Coord)>, ParseError> {
+        let vertices = path
+            .split("->")
+            .map(str::parse)
+            .collect::<Result<Vec<Coord>, _>>()?;
+        match vertices[..] {
+            [single] => Ok(vec![(single, single)]),
+            _ => Ok(vertices.windows(2).map(|w| (w[0], w[1])).collect()),
         }
     }
 
+    fn segment(start: Coord, end: Coord) -> impl Iterator<Item = Coord> {
+        use std::cmp::{max, min};
+
+        (min(start.x, end.x)..=max(start.x, end.x)).flat_map(move |x| {
+            (min(start.y, end.y)..=max(start.y, end.y)).map(move |y| Coord { x, y })
+        })
+    }
+
     #[cfg(test)]
     mod tests {
+        use super::frames::{Frame, FrameSink, Gif, ImageSequence, Pixel, Pnm, Terminal, Viewport};
+        use super::sources::Grain;
+        use super::{Bottom, Cave, Clog, Coord, DropResult, ParseError, RockError, Schedule, Tile};
+
+        const EXAMPLE: &str = "\
+498,4 -> 498,6 -> 496,6
+503,4 -> 502,4 -> 502,9 -> 494,9";
+
+        #[test]
+        fn example_part1() {
+            assert_eq!(24, super::part1(EXAMPLE));
+        }
+
+        #[test]
+        fn example_part2() {
+            assert_eq!(93, super::part2(EXAMPLE));
+        }
+
         #[test]
         fn real_part1() {
             assert_eq!(1001, super::part1(crate::INPUT));
@@ -167,10 +554,465 @@ mod day14 {
         fn real_part2() {
             assert_eq!(27976, super::part2(crate::INPUT));
         }
+
+        fn fill(mut cave: Cave, restart: bool) -> (u64, Cave) {
+            let mut count = 0;
+            loop {
+                if restart {
+                    cave.sources[0].path.clear();
+                }
+                match cave.drop() {
+                    DropResult::Rest(_) => count += 1,
+                    _ => return (count, cave),
+                }
+            }
+        }
+
+        #[test]
+        fn memo_matches_restarting() {
+            for bottom in [Bottom::Abyss, Bottom::Floor] {
+                let (count, cave) = fill(Cave::new(crate::INPUT, bottom).unwrap(), false);
+                let (restarted, naive) = fill(Cave::new(crate::INPUT, bottom).unwrap(), true);
+                assert_eq!(restarted, count);
+                assert_eq!(naive.minx, cave.minx);
+                assert_eq!(naive.columns, cave.columns);
+            }
+        }
+
+        #[test]
+        fn other_source() {
+            // far off to the left of any rock, it builds a pyramid on the floor
+            let cave = Cave::new(EXAMPLE, Bottom::Floor)
+                .unwrap()
+                .with_source(Coord { x: 0, y: 0 })
+                .unwrap();
+            let (count, cave) = fill(cave, false);
+            assert_eq!(11 * 11, count);
+            assert_eq!(-10, cave.minx);
+            assert_eq!(Tile::Sand, cave.tile(Coord { x: 10, y: 10 }));
+            assert_eq!(Tile::Air, cave.tile(Coord { x: 11, y: 10 }));
+
+            let mut cave = Cave::new(EXAMPLE, Bottom::Abyss)
+                .unwrap()
+                .with_source(Coord { x: 1000, y: 3 })
+                .unwrap();
+            assert_eq!(DropResult::Abyss, cave.drop());
+            let mut cave = Cave::new(EXAMPLE, Bottom::Abyss)
+                .unwrap()
+                .with_source(Coord { x: 500, y: 8 })
+                .unwrap();
+            assert_eq!(DropResult::Rest(Coord { x: 500, y: 8 }), cave.drop());
+            assert_eq!(DropResult::Clogged, cave.drop());
+        }
+
+        #[test]
+        fn rock_far_away() {
+            let input = "0,3 -> 2,3\n2000,5 -> 2000,6";
+            assert_eq!(0, super::part1(input));
+            assert_eq!(64, super::part2(input));
+        }
+
+        #[test]
+        fn several_sources() {
+            let mut cave = Cave::new(EXAMPLE, Bottom::Floor).unwrap();
+            let left = cave
+                .add_source(Coord { x: 490, y: 4 }, Schedule::ALWAYS)
+                .unwrap();
+            let slow = Schedule {
+                start: 10,
+                every: 4,
+                limit: Some(3),
+            };
+            let right = cave.add_source(Coord { x: 510, y: 0 }, slow).unwrap();
+            let mut from_right = 0;
+            for _ in 0..200 {
+                cave.tick(|id, _| from_right += (id == right) as u64);
+            }
+            assert_eq!(3, from_right);
+            assert_eq!(3, cave.stats(right).emitted);
+            assert_eq!(3, cave.stats(right).resting);
+            assert_eq!(None, cave.stats(right).clogged);
+
+            // the left pile is lower, it clogs first and then the main one piles over it
+            let clog = cave.stats(left).clogged.unwrap();
+            assert_eq!(Some(Grain { source: left, number: clog.tick + 1 }), clog.grain);
+            let main = cave.stats(0).clogged.unwrap();
+            assert!(main.tick > clog.tick);
+            assert_eq!(Some(Grain { source: 0, number: main.tick + 1 }), main.grain);
+
+            let sand = (480..530)
+                .flat_map(|x| (0..12).map(move |y| Coord { x, y }))
+                .filter(|&c| cave.tile(c) == Tile::Sand)
+                .count() as u64;
+            let resting: u64 = (0..3).map(|id| cave.stats(id).resting).sum();
+            assert_eq!(sand, resting);
+            assert_eq!(0, cave.stats(0).fallen);
+        }
+
+        #[test]
+        fn clogged_by_rock() {
+            let mut cave = Cave::new(EXAMPLE, Bottom::Abyss).unwrap();
+            cave.add_rock("499,0 -> 501,0").unwrap();
+            cave.tick(|_, _| {});
+            assert_eq!(DropResult::Clogged, cave.drop());
+            let clog = Clog {
+                tick: 0,
+                grain: None,
+            };
+            assert_eq!(Some(clog), cave.stats(0).clogged);
+            assert_eq!(0, cave.stats(0).emitted);
+        }
+
+        #[test]
+        fn edit_rock() {
+            // sand keeps dropping while rock comes and goes, and still ends where it would have
+            // if every grain started from the top
+            let edits = [
+                (5, true, "497,2 -> 503,2"),
+                (8, false, "498,2 -> 502,2"),
+                (10, true, "490,11 -> 510,11"),
+                (15, false, "494,9 -> 502,9 -> 502,4 -> 503,4"),
+                (20, true, "499,1 -> 499,3"),
+            ];
+            let mut memo = Cave::new(EXAMPLE, Bottom::Abyss).unwrap();
+            let mut naive = Cave::new(EXAMPLE, Bottom::Abyss).unwrap();
+            for grain in 0..60 {
+                for &(at, add, path) in &edits {
+                    if at == grain {
+                        for cave in [&mut memo, &mut naive] {
+                            match add {
+                                true => cave.add_rock(path).unwrap(),
+                                false => cave.remove_rock(path).unwrap(),
+                            }
+                        }
+                    }
+                }
+                naive.sources[0].path.clear();
+                assert_eq!(naive.drop(), memo.drop(), "grain {grain}");
+            }
+            assert_eq!(naive.columns, memo.columns);
+            assert_eq!(Tile::Rock, memo.tile(Coord { x: 490, y: 11 }));
+            assert_eq!(13, memo.depth);
+        }
+
+        #[test]
+        fn rock_syntax() {
+            let mut cave = Cave::new("", Bottom::Floor).unwrap();
+            let err = |vertex: &str| {
+                Err(RockError::Parse(ParseError {
+                    vertex: vertex.to_string(),
+                }))
+            };
+            assert_eq!(err("x"), cave.add_rock("1,2 -> x"));
+            assert_eq!(err("1,2 - 3,4"), cave.add_rock("1,2 - 3,4"));
+            assert_eq!(err("3,-4"), cave.add_rock("1,2 -> 3,-4"));
+            assert_eq!(Ok(()), cave.add_rock("  -3,2 ->-3,4  "));
+            assert_eq!(Tile::Rock, cave.tile(Coord { x: -3, y: 3 }));
+            assert_eq!(Ok(()), cave.remove_rock("-3,3"));
+            assert_eq!(Tile::Air, cave.tile(Coord { x: -3, y: 3 }));
+            assert_eq!(Tile::Rock, cave.tile(Coord { x: -3, y: 4 }));
+
+            let blank = Cave::new("498,4 -> 498,6\n\n503,4 -> 502,4", Bottom::Floor);
+            assert_eq!("", blank.err().unwrap().vertex);
+            let bad = Cave::new("498,4 -> 498,6\n503,4 -> 502", Bottom::Abyss);
+            assert_eq!("bad rock vertex \"502\"", bad.err().unwrap().to_string());
+        }
+
+        #[test]
+        fn floor_under_sand() {
+            let mut cave = Cave::new(EXAMPLE, Bottom::Floor).unwrap();
+            cave.add_rock("520,12 -> 521,12").unwrap();
+            assert_eq!(14, cave.depth);
+            // sand on the floor, rock can still go in above it but no longer below it
+            let (count, mut cave) = fill(cave, false);
+            assert_eq!(Tile::Sand, cave.tile(Coord { x: 500, y: 13 }));
+            assert_eq!(Ok(()), cave.add_rock("530,11"));
+            assert_eq!(Err(RockError::SandOnFloor), cave.add_rock("530,13"));
+            assert_eq!(Tile::Air, cave.tile(Coord { x: 530, y: 13 }));
+            assert_eq!(14, cave.depth);
+            assert!(count > 93);
+
+            // over the abyss nothing rests on the bottom, so it can always go down
+            let (_, mut cave) = fill(Cave::new(EXAMPLE, Bottom::Abyss).unwrap(), false);
+            assert_eq!(Ok(()), cave.add_rock("530,13"));
+            assert_eq!(15, cave.depth);
+        }
+
+        #[test]
+        fn source_above_cave() {
+            let mut cave = Cave::new(EXAMPLE, Bottom::Floor).unwrap();
+            let above = Coord { x: 500, y: -1 };
+            assert_eq!(None, cave.add_source(above, Schedule::ALWAYS));
+            let left = Coord { x: -5, y: 0 };
+            assert_eq!(Some(1), cave.add_source(left, Schedule::ALWAYS));
+            let cave = Cave::new(EXAMPLE, Bottom::Floor).unwrap();
+            assert!(cave.with_source(Coord { x: 500, y: -3 }).is_none());
+        }
+
+        #[test]
+        fn report() {
+            let args = |a: &[&str]| a.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+            let sources = ["sources", "floor", "source=490,4", "source=510,0,10,4,3"];
+            let out = super::report(EXAMPLE, &args(&sources)).unwrap();
+            let expected = "\
+source 0 at 500,0: emitted 82, resting 82, fallen 0, clogged on tick 81 by grain 82 of source 0
+source 1 at 490,4: emitted 42, resting 42, fallen 0, clogged on tick 41 by grain 42 of source 1
+source 2 at 510,0: emitted 3, resting 3, fallen 0, not clogged
+stopped after 82 ticks
+";
+            assert_eq!(expected, out);
+
+            let out = super::report(EXAMPLE, &args(&["sources", "floor", "rock=100:530,13"]));
+            let expected = "\
+tick 100: can't add rock 530,13: sand rests on the floor
+source 0 at 500,0: emitted 93, resting 93, fallen 0, clogged on tick 92 by grain 93 of source 0
+stopped after 101 ticks
+";
+            assert_eq!(Some(expected.to_string()), out);
+
+            // covered by rock, then freed to go on until it spills into the abyss like part 1
+            let edits = ["rock=3:499,0->501,0", "unrock=10:499,0->501,0"];
+            let abyss = [&["sources", "abyss"], &edits[..]].concat();
+            let out = super::report(EXAMPLE, &args(&abyss));
+            let expected = "\
+tick 3: add rock 499,0->501,0
+tick 10: remove rock 499,0->501,0
+source 0 at 500,0: emitted 25, resting 24, fallen 1, clogged on tick 3
+stopped after 32 ticks
+";
+            assert_eq!(Some(expected.to_string()), out);
+
+            let out = super::report(EXAMPLE, &args(&["sources", "abyss", "from=490,0"])).unwrap();
+            assert!(out.starts_with("source 0 at 490,0: emitted 1, resting 0, fallen 1,"));
+
+            for bad in [
+                &["part1"][..],
+                &["sources"],
+                &["sources", "sky"],
+                &["sources", "floor", "source=1"],
+                &["sources", "floor", "source=1,2,3"],
+                &["sources", "floor", "source=1,2,3,0"],
+                &["sources", "floor", "from=1,-2"],
+                &["sources", "floor", "rock=5:1,2->x"],
+                &["sources", "floor", "unrock=1,2"],
+            ] {
+                assert_eq!(None, super::report(EXAMPLE, &args(bad)), "{bad:?}");
+            }
+        }
+
+        const VIEW: Viewport = Viewport {
+            left: 494,
+            top: 0,
+            width: 10,
+            height: 10,
+        };
+
+        // Keeps every frame as text
+        struct Recorder(Vec<String>);
+
+        impl FrameSink for Recorder {
+            fn frame(&mut self, frame: &Frame) -> std::io::Result<()> {
+                let pixels = frame.render(&VIEW);
+                let rows = pixels.chunks(VIEW.width);
+                let text = rows.map(|r| r.iter().map(|p| p.ascii()).collect::<String>());
+                self.0.push(text.collect::<Vec<_>>().join("\n"));
+                Ok(())
+            }
+        }
+
+        #[test]
+        fn frames() {
+            let mut frames = Recorder(vec![]);
+            assert_eq!(24, super::part1_with(EXAMPLE, &mut frames).unwrap());
+            assert_eq!(25, frames.0.len());
+            let first = "\
+......+...
+......~...
+......~...
+......~...
+....#.~.##
+....#.~.#.
+..###.~.#.
+......~.#.
+......o.#.
+#########.";
+            assert_eq!(first, frames.0[0]);
+            // the last grain falls off the left, past the view
+            let last = "\
+......+...
+......~...
+.....~o...
+....~ooo..
+...~#ooo##
+..~o#ooo#.
+.~###ooo#.
+.~..oooo#.
+~o.ooooo#.
+#########.";
+            assert_eq!(last, frames.0[24]);
+
+            let mut frames = Recorder(vec![]);
+            assert_eq!(93, super::part2_with(EXAMPLE, &mut frames).unwrap());
+            // the last frame is the drop that found the source clogged, like the abyss above
+            assert_eq!(94, frames.0.len());
+            assert_eq!(frames.0[92], frames.0[93]);
+
+            let fit = Viewport {
+                left: 489,
+                top: 0,
+                width: 23,
+                height: 12,
+            };
+            let cave = Cave::new(EXAMPLE, Bottom::Floor).unwrap();
+            assert_eq!(fit, Viewport::fit(&cave));
+        }
+
+        #[test]
+        fn terminal() {
+            let mut out = vec![];
+            let mut sink = Terminal::new(&mut out, VIEW, 1000);
+            super::part1_with(EXAMPLE, &mut sink).unwrap();
+            let out = String::from_utf8(out).unwrap();
+            assert!(out.starts_with("\x1b[2J\x1b[H......+...\n......~...\n"));
+            assert_eq!(25, out.matches("\x1b[H").count());
+            assert_eq!(25 * 10, out.lines().count());
+        }
+
+        #[test]
+        fn image_sequence() {
+            let dir = std::env::temp_dir().join(format!("day14-frames-{}", std::process::id()));
+            for (format, ext, magic, depth) in
+                [(Pnm::Ppm, "ppm", "P6", 3), (Pnm::Pgm, "pgm", "P5", 1)]
+            {
+                std::fs::create_dir_all(&dir).unwrap();
+                let mut sink = ImageSequence::new(&dir, format, VIEW, 2);
+                super::part1_with(EXAMPLE, &mut sink).unwrap();
+                let mut names: Vec<_> = std::fs::read_dir(&dir)
+                    .unwrap()
+                    .map(|e| e.unwrap().file_name().into_string().unwrap())
+                    .collect();
+                names.sort();
+                assert_eq!(25, names.len());
+                assert_eq!(format!("frame000024.{ext}"), names[24]);
+
+                let image = std::fs::read(dir.join(&names[0])).unwrap();
+                let header = format!("{magic}\n20 20\n255\n");
+                assert!(image.starts_with(header.as_bytes()));
+                assert_eq!(header.len() + 20 * 20 * depth, image.len());
+                std::fs::remove_dir_all(&dir).unwrap();
+            }
+        }
+
+        #[test]
+        fn gif() {
+            let mut out = vec![];
+            let mut sink = Gif::new(&mut out, VIEW, 3, 5).unwrap();
+            let mut frames = Recorder(vec![]);
+            super::part1_with(EXAMPLE, &mut sink).unwrap();
+            super::part1_with(EXAMPLE, &mut frames).unwrap();
+            let cave = Cave::new(EXAMPLE, Bottom::Abyss).unwrap();
+            let late = sink.frame(&Frame::new(&cave, 0, &DropResult::Abyss));
+            assert_eq!("the GIF is finished", late.unwrap_err().to_string());
+
+            let mut options = ::gif::DecodeOptions::new();
+            options.set_color_output(::gif::ColorOutput::Indexed);
+            let mut decoder = options.read_info(&out[..]).unwrap();
+            assert_eq!((30, 30), (decoder.width(), decoder.height()));
+            let palette = decoder.global_palette().unwrap().to_vec();
+            for (i, p) in Pixel::ALL.iter().enumerate() {
+                assert_eq!(p.rgb(), palette[i * 3..i * 3 + 3]);
+            }
+            let mut count = 0;
+            while let Some(frame) = decoder.read_next_frame().unwrap() {
+                assert_eq!(5, frame.delay);
+                // every third pixel of every third row, back to the recorded text
+                let text: Vec<String> = frame
+                    .buffer
+                    .chunks(30)
+                    .step_by(3)
+                    .map(|row| {
+                        let row = row.iter().step_by(3);
+                        row.map(|&i| Pixel::ALL[i as usize].ascii()).collect()
+                    })
+                    .collect();
+                assert_eq!(frames.0[count], text.join("\n"));
+                count += 1;
+            }
+            assert_eq!(25, count);
+        }
+
+        #[test]
+        fn animate() {
+            let args = |a: &[&str]| a.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+            let path = std::env::temp_dir().join(format!("day14-{}.gif", std::process::id()));
+            let gif = ["frames", "part2", "gif", path.to_str().unwrap(), "1", "3"];
+            assert_eq!(93, super::animate(EXAMPLE, &args(&gif)).unwrap().unwrap());
+            let out = std::fs::read(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+            let decoder = ::gif::DecodeOptions::new().read_info(&out[..]).unwrap();
+            // the pile on the floor reaches 11 either side of the source
+            let fit = Viewport::fit(&Cave::new(EXAMPLE, Bottom::Floor).unwrap());
+            assert_eq!((23, 12), (decoder.width(), decoder.height()));
+            assert_eq!((23, 12), (fit.width, fit.height));
+
+            for bad in [
+                &["frames"][..],
+                &["frames", "part3", "terminal"],
+                &["frames", "part1"],
+                &["frames", "part1", "terminal", "fast"],
+                &["frames", "part1", "ppm"],
+                &["frames", "part1", "pgm", "dir", "0"],
+                &["frames", "part1", "gif", "a.gif", "2", "5", "7"],
+                &["frames", "part1", "png", "dir"],
+            ] {
+                assert!(super::animate(EXAMPLE, &args(bad)).is_none(), "{bad:?}");
+            }
+        }
+
+        #[test]
+        fn gif_compression() {
+            // enough varied pixels to fill the code table several times over
+            let (width, height) = (300u16, 200u16);
+            let mut state = 12345u32;
+            let frames: Vec<Vec<u8>> = (0..3)
+                .map(|f| {
+                    (0..width as usize * height as usize)
+                        .map(|i| {
+                            state = state.wrapping_mul(1103515245).wrapping_add(12345);
+                            match f {
+                                0 => (state >> 16) as u8 % 5,
+                                1 => (i / 7 % 5) as u8,
+                                _ => 0,
+                            }
+                        })
+                        .collect()
+                })
+                .collect();
+            let mut out = vec![];
+            let palette = [
+                [0, 0, 0],
+                [255, 0, 0],
+                [0, 255, 0],
+                [0, 0, 255],
+                [255, 255, 255],
+            ];
+            let mut encoder =
+                super::gif::GifEncoder::new(&mut out, width, height, &palette).unwrap();
+            for pixels in &frames {
+                encoder.add_frame(pixels, 10).unwrap();
+            }
+            encoder.finish().unwrap();
+
+            let mut options = ::gif::DecodeOptions::new();
+            options.set_color_output(::gif::ColorOutput::Indexed);
+            let mut decoder = options.read_info(&out[..]).unwrap();
+            for pixels in &frames {
+                let frame = decoder.read_next_frame().unwrap().unwrap();
+                assert_eq!(&pixels[..], &frame.buffer[..]);
+            }
+            assert!(decoder.read_next_frame().unwrap().is_none());
+        }
     }
 }
 
 const INPUT: &str = include_str!("input.txt");
-// const INPUT: &str = "\
-// 498,4 -> 498,6 -> 496,6
-// 503,4 -> 502,4 -> 502,9 -> 494,9";
@@ -0,0 +1,127 @@
+// Queries over the whole sensor network. Each row of a region is one interval set, and rows are
+// taken a band at a time where that helps.
+
+use super::{row_coverage, Bounds, Coord, IntervalSet, Sensor};
+
+// The part of row y inside the region that some sensor covers
+fn row_in(sensors: &[Sensor], bounds: &Bounds, y: isize) -> IntervalSet {
+    let mut region = IntervalSet::new();
+    region.insert(bounds.min.x, bounds.max.x);
+    row_coverage(sensors, y).intersection(&region)
+}
+
+// Splits the region's rows into bands, as first and last row, where the covered part of a row
+// grows or shrinks by the same amount from one row to the next.
+//
+// Down a sensor's diamond each end of its interval moves one cell a row, so every end is a line
+// c + y or c - y, and the edges of the region are lines that stay put. The covered length only
+// changes pace where a sensor's diamond starts, turns or stops, or where two of those lines meet
+// or come within a cell of each other, so the intervals start or stop overlapping or touching.
+fn bands(sensors: &[Sensor], bounds: &Bounds) -> Vec<(isize, isize)> {
+    let (top, bottom) = (bounds.min.y, bounds.max.y);
+    let mut lines = vec![(bounds.min.x, 0), (bounds.max.x, 0)];
+    let mut breaks = vec![top, bottom + 1];
+    for s in sensors {
+        let (Coord { x, y }, r) = (s.coord, s.radius());
+        lines.extend([
+            (x - r + y, -1),
+            (x + r - y, 1),
+            (x - r - y, 1),
+            (x + r + y, -1),
+        ]);
+        breaks.extend([y - r, y, y + 1, y + r + 1]);
+    }
+    for (i, &(c1, m1)) in lines.iter().enumerate() {
+        for &(c2, m2) in lines.iter().skip(i + 1).filter(|&&(_, m2)| m2 != m1) {
+            for gap in -1..=1 {
+                // c1 + m1 y = c2 + m2 y + gap
+                let at = (c2 - c1 + gap).div_euclid(m1 - m2);
+                breaks.extend([at, at + 1]);
+            }
+        }
+    }
+    breaks.retain(|y| (top..=bottom + 1).contains(y));
+    breaks.sort_unstable();
+    breaks.dedup();
+    breaks.windows(2).map(|w| (w[0], w[1] - 1)).collect()
+}
+
+// Cells of the region within reach of at least one sensor, beacons included
+pub fn covered_area(sensors: &[Sensor], bounds: &Bounds) -> u64 {
+    bands(sensors, bounds)
+        .into_iter()
+        .map(|(first, last)| {
+            let rows = (last - first + 1) as u64;
+            let (a, b) = (
+                row_in(sensors, bounds, first),
+                row_in(sensors, bounds, last),
+            );
+            // the rows in between step evenly from the first to the last
+            (a.len() + b.len()) * rows / 2
+        })
+        .sum()
+}
+
+// Row by row, left to right
+pub fn uncovered(sensors: &[Sensor], bounds: &Bounds) -> Vec<Coord> {
+    let width = (bounds.min.x.abs_diff(bounds.max.x) as u64).saturating_add(1);
+    bands(sensors, bounds)
+        .into_iter()
+        // a band that's covered at both ends is covered all the way through
+        .filter(|&(first, last)| {
+            row_in(sensors, bounds, first).len() < width
+                || row_in(sensors, bounds, last).len() < width
+        })
+        .flat_map(|(first, last)| first..=last)
+        .flat_map(|y| {
+            let free = row_coverage(sensors, y).complement(bounds.min.x, bounds.max.x);
+            free.spans()
+                .iter()
+                .flat_map(|&(s, e)| s..=e)
+                .map(move |x| Coord { x, y })
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+pub fn covering<'s>(sensors: &'s [Sensor], c: &Coord) -> impl Iterator<Item = &'s Sensor> {
+    let c = *c;
+    sensors.iter().filter(move |s| s.covers(&c))
+}
+
+// One character per `scale` by `scale` block of the region: '#' if it's all covered, '.' if none
+// of it is, '+' otherwise. Blocks with a sensor show 'S', or else 'B' if they have a beacon.
+pub fn render(sensors: &[Sensor], bounds: &Bounds, scale: usize) -> String {
+    assert!(scale > 0, "a block is at least one cell wide");
+    let step = scale as isize;
+    let mut res = String::new();
+    for top in (bounds.min.y..=bounds.max.y).step_by(scale) {
+        let bottom = (top + step - 1).min(bounds.max.y);
+        let rows: Vec<IntervalSet> = (top..=bottom).map(|y| row_in(sensors, bounds, y)).collect();
+        for left in (bounds.min.x..=bounds.max.x).step_by(scale) {
+            let right = (left + step - 1).min(bounds.max.x);
+            let block = Bounds {
+                min: Coord { x: left, y: top },
+                max: Coord {
+                    x: right,
+                    y: bottom,
+                },
+            };
+            let free: u64 = rows.iter().map(|r| r.complement(left, right).len()).sum();
+            let cells = (right - left + 1) as u64 * (bottom - top + 1) as u64;
+            res.push(if sensors.iter().any(|s| block.contains(&s.coord)) {
+                'S'
+            } else if sensors.iter().any(|s| block.contains(&s.beacon)) {
+                'B'
+            } else if free == 0 {
+                '#'
+            } else if free == cells {
+                '.'
+            } else {
+                '+'
+            });
+        }
+        res.push('\n');
+    }
+    res
+}
@@ -0,0 +1,69 @@
+// Finding a cell no sensor covers.
+//
+// Turned 45 degrees, to u = x + y and v = x - y, a sensor's diamond is a square: |du| <= r and
+// |dv| <= r. A lone uncovered cell sits right outside the diamonds around it, so it's on the
+// lines one past their edges, u = su +- (r + 1) or v = sv +- (r + 1).
+//
+// Take the uncovered cell with the lowest u, and the lowest v among those. The cells before it,
+// left and above it, are covered or outside the region, which puts it on one of those lines, or
+// on the region's edge. Either it's a corner, or a line crosses the edge there, or it's where a
+// u line and a v line cross. When those cross between cells (u + v is odd), it's one of the cells
+// right next to the crossing. Only those points are checked, so there's never a need to scan.
+
+use super::{Bounds, Coord, Sensor};
+
+pub fn find_gap(sensors: &[Sensor], bounds: &Bounds) -> Option<Coord> {
+    let uncovered = |c: &Coord| bounds.contains(c) && sensors.iter().all(|s| !s.covers(c));
+    candidates(sensors, bounds).into_iter().find(uncovered)
+}
+
+fn candidates(sensors: &[Sensor], bounds: &Bounds) -> Vec<Coord> {
+    let outside = |center: isize, s: &Sensor| {
+        let r = s.radius() + 1;
+        [center - r, center + r]
+    };
+    let us: Vec<isize> = sensors
+        .iter()
+        .flat_map(|s| outside(s.coord.x + s.coord.y, s))
+        .collect();
+    let vs: Vec<isize> = sensors
+        .iter()
+        .flat_map(|s| outside(s.coord.x - s.coord.y, s))
+        .collect();
+    let (min, max) = (bounds.min, bounds.max);
+
+    let mut res = vec![
+        min,
+        max,
+        Coord { x: min.x, y: max.y },
+        Coord { x: max.x, y: min.y },
+    ];
+    for &u in &us {
+        for &v in &vs {
+            if (u + v) % 2 == 0 {
+                res.push(Coord {
+                    x: (u + v) / 2,
+                    y: (u - v) / 2,
+                });
+            } else {
+                for (u, v) in [(u - 1, v), (u + 1, v), (u, v - 1), (u, v + 1)] {
+                    res.push(Coord {
+                        x: (u + v) / 2,
+                        y: (u - v) / 2,
+                    });
+                }
+            }
+        }
+        for (x, y) in [(min.x, min.y), (max.x, max.y)] {
+            res.push(Coord { x, y: u - x });
+            res.push(Coord { x: u - y, y });
+        }
+    }
+    for &v in &vs {
+        for (x, y) in [(min.x, min.y), (max.x, max.y)] {
+            res.push(Coord { x, y: x - v });
+            res.push(Coord { x: v + y, y });
+        }
+    }
+    res
+}
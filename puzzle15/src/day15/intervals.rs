@@ -0,0 +1,115 @@
+// A set of integers, kept as sorted inclusive spans that neither overlap nor touch.
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IntervalSet {
+    spans: Vec<(isize, isize)>,
+}
+
+impl IntervalSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn spans(&self) -> &[(isize, isize)] {
+        &self.spans
+    }
+
+    // Adds start..=end, nothing if it's empty
+    pub fn insert(&mut self, start: isize, end: isize) {
+        if start > end {
+            return;
+        }
+        // the spans that overlap or touch the new one
+        let first = self
+            .spans
+            .partition_point(|&(_, e)| e < start.saturating_sub(1));
+        let last = self
+            .spans
+            .partition_point(|&(s, _)| s <= end.saturating_add(1));
+        let merged = match &self.spans[first..last] {
+            [] => (start, end),
+            touching => (
+                start.min(touching[0].0),
+                end.max(touching[touching.len() - 1].1),
+            ),
+        };
+        self.spans.splice(first..last, [merged]);
+    }
+
+    pub fn remove(&mut self, point: isize) {
+        let i = self.spans.partition_point(|&(_, e)| e < point);
+        let Some(&(start, end)) = self.spans.get(i).filter(|&&(s, _)| s <= point) else {
+            return;
+        };
+        // either side is empty when point is the span's end, which may be isize::MIN or MAX
+        let before = point.checked_sub(1).map(|e| (start, e));
+        let after = point.checked_add(1).map(|s| (s, end));
+        let split = [before, after].into_iter().flatten();
+        self.spans.splice(i..=i, split.filter(|(s, e)| s <= e));
+    }
+
+    pub fn intersection(&self, other: &IntervalSet) -> IntervalSet {
+        let mut spans = vec![];
+        let (mut a, mut b) = (self.spans.iter().peekable(), other.spans.iter().peekable());
+        while let (Some(&&(s1, e1)), Some(&&(s2, e2))) = (a.peek(), b.peek()) {
+            let (s, e) = (s1.max(s2), e1.min(e2));
+            if s <= e {
+                spans.push((s, e));
+            }
+            // drop whichever ends first, the other may still overlap the next one
+            if e1 < e2 {
+                a.next();
+            } else {
+                b.next();
+            }
+        }
+        IntervalSet { spans }
+    }
+
+    // Everything in start..=end that's not in the set
+    pub fn complement(&self, start: isize, end: isize) -> IntervalSet {
+        let mut spans = vec![];
+        let mut cur = start;
+        for &(s, e) in &self.spans {
+            if cur > end || s > end {
+                break;
+            }
+            if s > cur {
+                spans.push((cur, s - 1));
+            }
+            // a span that runs to isize::MAX leaves nothing after it
+            let Some(next) = e.checked_add(1) else {
+                return IntervalSet { spans };
+            };
+            cur = cur.max(next);
+        }
+        if cur <= end {
+            spans.push((cur, end));
+        }
+        IntervalSet { spans }
+    }
+
+    // How many integers are in the set, at most u64::MAX even when it's all of isize
+    pub fn len(&self) -> u64 {
+        self.spans
+            .iter()
+            .map(|&(s, e)| (s.abs_diff(e) as u64).saturating_add(1))
+            .fold(0, u64::saturating_add)
+    }
+}
+
+impl FromIterator<(isize, isize)> for IntervalSet {
+    // Sorts the spans once and merges them in a single pass
+    fn from_iter<T: IntoIterator<Item = (isize, isize)>>(iter: T) -> Self {
+        let mut sorted: Vec<_> = iter.into_iter().filter(|(s, e)| s <= e).collect();
+        sorted.sort_unstable();
+        let mut spans: Vec<(isize, isize)> = Vec::with_capacity(sorted.len());
+        for (s, e) in sorted {
+            match spans.last_mut() {
+                Some(last) if s <= last.1.saturating_add(1) => last.1 = last.1.max(e),
+                _ => spans.push((s, e)),
+            }
+        }
+        IntervalSet { spans }
+    }
+}
@@ -3,6 +3,16 @@ extern crate pest;
 extern crate pest_derive;
 
 fn main() {
+    // `puzzle15 area|uncovered x,y x,y` counts the covered cells of the region between two
+    // corners instead, or lists the cells no sensor reaches, `puzzle15 covering x,y` lists the
+    // sensors that reach a cell and `puzzle15 render x,y x,y [scale]` maps the region, one
+    // character for every `scale` by `scale` block
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(report) = day15::report(INPUT, &args) {
+        print!("{report}");
+        return;
+    }
+
     for func in [day15::part1, day15::part2] {
         let start = std::time::Instant::now();
         let res = func(INPUT);
@@ -13,49 +23,113 @@ fn main() {
 }
 
 mod day15 {
-    pub fn part1(input: &str) -> u64 {
-        // sensor at s = (sx, sy) receiving beacon at b = (bx, by) has exclusion radius r = dist(s,b).
-        // row y has distance from sensor at s of d = row_dist(y, s) = |y - sy|
-        // if d <= r, then let diff = r - d, and the sensor exclude [sx - diff, sx + diff] from row y
+    use std::fmt::{self, Write};
 
-        let sensors = parse_sensors(input);
-        let mut detectable = collect_detectable_ranges(2_000_000, &sensors, true);
+    use intervals::IntervalSet;
+
+    mod coverage;
+    mod gap;
+    mod intervals;
 
-        detectable.count() as u64
+    pub fn part1(input: &str) -> u64 {
+        let sensors = parse_sensors(input);
+        no_beacon_count(&sensors, 2_000_000)
     }
 
     pub fn part2(input: &str) -> u64 {
         const LIMIT: isize = 4_000_000;
         let sensors = parse_sensors(input);
-        for y in 0..=LIMIT {
-            let mut detectable = collect_detectable_ranges(y, &sensors, false);
-            if let Some(x) = detectable.first_free(0, LIMIT) {
-                return (x * LIMIT + y) as u64;
+        let bounds = Bounds {
+            min: Coord { x: 0, y: 0 },
+            max: Coord { x: LIMIT, y: LIMIT },
+        };
+        let beacon = gap::find_gap(&sensors, &bounds).expect("no room for the distress beacon");
+        tuning_frequency(&beacon) as u64
+    }
+
+    pub fn report(input: &str, args: &[String]) -> Option<String> {
+        let (cmd, rest) = args.split_first()?;
+        let sensors = || parse_sensors(input);
+        let mut out = String::new();
+        match (cmd.as_str(), rest) {
+            ("covering", [cell]) => {
+                let cell = parse_coord(cell)?;
+                let sensors = sensors();
+                for s in coverage::covering(&sensors, &cell) {
+                    writeln!(out, "sensor at {}, radius {}", s.coord, s.radius()).unwrap();
+                }
+                if out.is_empty() {
+                    writeln!(out, "no sensor covers {cell}").unwrap();
+                }
+            }
+            ("area" | "uncovered" | "render", [min, max, scale @ ..]) => {
+                let bounds = Bounds {
+                    min: parse_coord(min)?,
+                    max: parse_coord(max)?,
+                };
+                if bounds.min.x > bounds.max.x || bounds.min.y > bounds.max.y {
+                    return None;
+                }
+                let sensors = sensors();
+                match (cmd.as_str(), scale) {
+                    ("area", []) => {
+                        let area = coverage::covered_area(&sensors, &bounds);
+                        writeln!(out, "{area} cells covered").unwrap();
+                    }
+                    ("uncovered", []) => {
+                        for c in coverage::uncovered(&sensors, &bounds) {
+                            writeln!(out, "{c}").unwrap();
+                        }
+                    }
+                    ("render", []) => out = coverage::render(&sensors, &bounds, 1),
+                    ("render", [scale]) => {
+                        let scale = scale.parse().ok().filter(|&s| s > 0)?;
+                        out = coverage::render(&sensors, &bounds, scale);
+                    }
+                    _ => return None,
+                }
             }
+            _ => return None,
         }
-        0
+        Some(out)
+    }
+
+    fn parse_coord(s: &str) -> Option<Coord> {
+        let (x, y) = s.split_once(',')?;
+        Some(Coord {
+            x: x.parse().ok()?,
+            y: y.parse().ok()?,
+        })
+    }
+
+    fn tuning_frequency(beacon: &Coord) -> isize {
+        beacon.x * 4_000_000 + beacon.y
     }
 
     fn parse_sensors(input: &str) -> Vec<Sensor> {
         input.lines().map(Sensor::from).collect()
     }
 
-    fn collect_detectable_ranges(y: isize, sensors: &Vec<Sensor>, exclude_devices: bool) -> Ranges {
-        let mut ranges = Ranges::new();
-        if exclude_devices {
-            for s in sensors.iter().filter(|s| s.beacon.y == y) {
-                ranges.set_excluded(s.beacon.x);
-            }
-        }
-        for s in sensors {
-            let r = s.radius();
-            let d = s.dist(y);
-            if d <= r {
-                let diff = r - d;
-                ranges.add_range(s.coord.x - diff, s.coord.x + diff);
-            }
+    // sensor at s = (sx, sy) receiving beacon at b = (bx, by) has exclusion radius r = dist(s,b).
+    // row y has distance from sensor at s of d = row_dist(y, s) = |y - sy|
+    // if d <= r, then let diff = r - d, and the sensor covers [sx - diff, sx + diff] of row y
+    fn row_coverage(sensors: &[Sensor], y: isize) -> IntervalSet {
+        sensors
+            .iter()
+            .map(|s| {
+                let diff = s.radius() - s.dist(y);
+                (s.coord.x - diff, s.coord.x + diff)
+            })
+            .collect()
+    }
+
+    // Covered cells of row y that aren't a known beacon
+    fn no_beacon_count(sensors: &[Sensor], y: isize) -> u64 {
+        let mut covered = row_coverage(sensors, y);
+        for s in sensors.iter().filter(|s| s.beacon.y == y) {
+            covered.remove(s.beacon.x);
         }
-        ranges
+        covered.len()
     }
 
     #[derive(Debug)]
@@ -69,6 +143,10 @@ mod day15 {
             self.coord.dist(&self.beacon)
         }
 
+        fn covers(&self, c: &Coord) -> bool {
+            self.coord.dist(c) <= self.radius()
+        }
+
         fn dist(&self, row: isize) -> isize {
             self.coord.dist(&Coord {
                 y: row,
@@ -99,114 +177,196 @@ mod day15 {
         num = { "-"? ~ ASCII_DIGIT+ }"#]
     struct SensorParser;
 
-    #[derive(Debug)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
     struct Coord {
         x: isize,
         y: isize,
     }
 
+    impl fmt::Display for Coord {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{},{}", self.x, self.y)
+        }
+    }
+
     impl Coord {
         fn dist(&self, other: &Coord) -> isize {
             (self.x - other.x).abs() + (self.y - other.y).abs()
         }
     }
 
+    // Inclusive on both ends
     #[derive(Debug)]
-    struct Rng(isize, isize);
+    struct Bounds {
+        min: Coord,
+        max: Coord,
+    }
 
-    #[derive(Debug)]
-    struct Ranges {
-        rs: Vec<Rng>,
-        excluded: Vec<isize>,
+    impl Bounds {
+        fn contains(&self, c: &Coord) -> bool {
+            (self.min.x..=self.max.x).contains(&c.x) && (self.min.y..=self.max.y).contains(&c.y)
+        }
     }
 
-    impl Ranges {
-        fn new() -> Self {
-            Self {
-                rs: vec![],
-                excluded: vec![],
+    #[cfg(test)]
+    mod tests {
+        use std::collections::BTreeSet;
+
+        use proptest::prelude::*;
+
+        use super::{coverage, gap::find_gap, parse_sensors, Bounds, Coord, IntervalSet, Sensor};
+
+        const EXAMPLE: &str = "\
+Sensor at x=2, y=18: closest beacon is at x=-2, y=15
+Sensor at x=9, y=16: closest beacon is at x=10, y=16
+Sensor at x=13, y=2: closest beacon is at x=15, y=3
+Sensor at x=12, y=14: closest beacon is at x=10, y=16
+Sensor at x=10, y=20: closest beacon is at x=10, y=16
+Sensor at x=14, y=17: closest beacon is at x=10, y=16
+Sensor at x=8, y=7: closest beacon is at x=2, y=10
+Sensor at x=2, y=0: closest beacon is at x=2, y=10
+Sensor at x=0, y=11: closest beacon is at x=2, y=10
+Sensor at x=20, y=14: closest beacon is at x=25, y=17
+Sensor at x=17, y=20: closest beacon is at x=21, y=22
+Sensor at x=16, y=7: closest beacon is at x=15, y=3
+Sensor at x=14, y=3: closest beacon is at x=15, y=3
+Sensor at x=20, y=1: closest beacon is at x=15, y=3";
+
+        fn square(max: isize) -> Bounds {
+            Bounds {
+                min: Coord { x: 0, y: 0 },
+                max: Coord { x: max, y: max },
             }
         }
 
-        // exclude e from all the ranges (by splitting ranges that contain them into 2 if e is in the middle)
-        fn set_excluded(&mut self, e: isize) {
-            self.excluded.push(e);
+        #[test]
+        fn example_part1() {
+            let sensors = parse_sensors(EXAMPLE);
+            assert_eq!(26, super::no_beacon_count(&sensors, 10));
+            // nothing covers row -100, only the sensors at 2,18 and 17,20 reach row 25
+            assert_eq!(0, super::no_beacon_count(&sensors, -100));
+            assert_eq!(4, super::no_beacon_count(&sensors, 25));
+        }
+
+        #[test]
+        fn example_part2() {
+            let sensors = parse_sensors(EXAMPLE);
+            let beacon = find_gap(&sensors, &square(20)).unwrap();
+            assert_eq!(Coord { x: 14, y: 11 }, beacon);
+            assert_eq!(56000011, super::tuning_frequency(&beacon));
+            assert_eq!(None, find_gap(&sensors, &square(10)));
         }
 
-        fn add_range(&mut self, start: isize, end: isize) {
-            let start = if self.excluded.contains(&start) {
-                start + 1
-            } else {
-                start
+        #[test]
+        fn example_coverage() {
+            let sensors = parse_sensors(EXAMPLE);
+            assert_eq!(21 * 21 - 1, coverage::covered_area(&sensors, &square(20)));
+            assert_eq!(
+                vec![Coord { x: 14, y: 11 }],
+                coverage::uncovered(&sensors, &square(20))
+            );
+            let far = Bounds {
+                min: Coord { x: 100, y: -5 },
+                max: Coord { x: 102, y: 5 },
             };
-            let end = if self.excluded.contains(&end) {
-                end - 1
-            } else {
-                end
+            assert_eq!(0, coverage::covered_area(&sensors, &far));
+            assert_eq!(33, coverage::uncovered(&sensors, &far).len());
+
+            let covering = |x, y| -> Vec<Coord> {
+                coverage::covering(&sensors, &Coord { x, y })
+                    .map(|s| s.coord)
+                    .collect()
             };
-            if end - start >= 0 {
-                let mut added = false;
-                for e in self.excluded.clone() {
-                    if start < e && end > e {
-                        self.add_range(start, e - 1);
-                        self.add_range(e + 1, end);
-                        added = true;
-                    }
-                }
-                if !added {
-                    self.rs.push(Rng(start, end));
-                }
-            }
+            assert_eq!(Vec::<Coord>::new(), covering(14, 11));
+            assert_eq!(vec![Coord { x: 2, y: 0 }], covering(-5, 3));
+            assert_eq!(
+                vec![Coord { x: 8, y: 7 }, Coord { x: 2, y: 0 }],
+                covering(2, 5)
+            );
+
+            let map = "\
+.+++......
+++#++.++..
++#S#++#S+.
+++###S##+.
+.+##S#S+..
+.+S##+##+.
+.B##SS#SB+
+++S#S#S++.
+.++++++B..
+......+...
+";
+            let around = Bounds {
+                min: Coord { x: -8, y: -10 },
+                max: Coord { x: 28, y: 26 },
+            };
+            assert_eq!(map, coverage::render(&sensors, &around, 4));
+            // the puzzle's own picture, with the distress beacon in the one gap
+            let full = coverage::render(&sensors, &square(20), 1);
+            assert_eq!(Some("S#############.######"), full.lines().nth(11));
         }
 
-        fn count(&mut self) -> usize {
-            self.rs.sort_by_key(|r| r.0);
-            let mut res = 0;
-            if !self.rs.is_empty() {
-                let mut cur = self.rs[0].0;
-                for rng in &self.rs {
-                    if rng.0 > cur {
-                        cur = rng.0;
-                        res += 1;
-                    }
-                    if rng.1 >= cur {
-                        let added = rng.1 - cur;
-                        res += added;
-                        cur = rng.1;
-                    }
-                }
+        #[test]
+        fn report() {
+            let args = |a: &[&str]| a.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+            let report = |a: &[&str]| super::report(EXAMPLE, &args(a)).unwrap();
+            assert_eq!("440 cells covered\n", report(&["area", "0,0", "20,20"]));
+            assert_eq!("14,11\n", report(&["uncovered", "0,0", "20,20"]));
+            let expected = "sensor at 8,7, radius 9\nsensor at 2,0, radius 10\n";
+            assert_eq!(expected, report(&["covering", "2,5"]));
+            assert_eq!("no sensor covers 14,11\n", report(&["covering", "14,11"]));
+            let map = report(&["render", "-8,-10", "28,26", "4"]);
+            assert!(map.starts_with(".+++......\n++#++.++..\n"));
+            let full = report(&["render", "0,0", "20,20"]);
+            assert_eq!(Some("S#############.######"), full.lines().nth(11));
+
+            for bad in [
+                &["area", "0,0"][..],
+                &["area", "20,20", "0,0"],
+                &["uncovered", "0,0", "20,20", "4"],
+                &["render", "0,0", "20,20", "0"],
+                &["covering", "14"],
+                &["beacons"],
+            ] {
+                assert_eq!(None, super::report(EXAMPLE, &args(bad)), "{bad:?}");
             }
-            res as usize + 1 // the first element isn't counted
         }
 
-        fn first_free(&mut self, start: isize, end: isize) -> Option<isize> {
-            self.rs.sort_by_key(|r| r.0);
-            let mut rng = self.rs.iter();
-            let mut cur = start; // points to the next position that may not be detectable (outside the processed ranges)
-            for r in &mut rng {
-                if r.1 <= cur {
-                    continue;
-                }
-                if r.0 > cur {
-                    return Some(cur);
-                }
-                if r.1 > cur {
-                    cur = r.1 + 1;
-                }
-                if cur > end {
-                    break;
+        #[test]
+        fn random_gaps() {
+            let mut state = 7u64;
+            let mut rand = |n: isize| {
+                state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                (state >> 33) as isize % n
+            };
+            for _ in 0..300 {
+                let bounds = square(rand(20) + 1);
+                let sensors: Vec<Sensor> = (0..rand(8) + 1)
+                    .map(|_| {
+                        let coord = Coord { x: rand(24) - 2, y: rand(24) - 2 };
+                        let r = rand(12);
+                        let dx = rand(r + 1);
+                        let beacon = Coord { x: coord.x + dx, y: coord.y - (r - dx) };
+                        Sensor { coord, beacon }
+                    })
+                    .collect();
+                let uncovered: Vec<Coord> = (bounds.min.y..=bounds.max.y)
+                    .flat_map(|y| (bounds.min.x..=bounds.max.x).map(move |x| Coord { x, y }))
+                    .filter(|c| sensors.iter().all(|s| !s.covers(c)))
+                    .collect();
+                assert_eq!(uncovered, coverage::uncovered(&sensors, &bounds));
+                let cells = (bounds.max.x + 1) * (bounds.max.y + 1);
+                assert_eq!(
+                    cells as u64 - uncovered.len() as u64,
+                    coverage::covered_area(&sensors, &bounds)
+                );
+                match find_gap(&sensors, &bounds) {
+                    None => assert!(uncovered.is_empty(), "{sensors:?} in {bounds:?}"),
+                    Some(c) => assert!(uncovered.contains(&c), "{c:?} for {sensors:?}"),
                 }
             }
-            if cur <= end {
-                return Some(cur);
-            }
-
-            None
         }
-    }
 
-    #[cfg(test)]
-    mod tests {
         #[test]
         fn real_part1() {
             assert_eq!(5607466, super::part1(crate::INPUT));
@@ -214,6 +374,105 @@ mod day15 {
         #[test]
         fn real_part2() {
             assert_eq!(12543202766584, super::part2(crate::INPUT));
+            // every cell but the distress beacon's, without going row by row
+            let sensors = parse_sensors(crate::INPUT);
+            let area = coverage::covered_area(&sensors, &square(4_000_000));
+            assert_eq!(4_000_001 * 4_000_001 - 1, area);
+        }
+
+        #[test]
+        fn interval_set() {
+            let mut set: IntervalSet = [(5, 8), (1, 2), (3, 4), (12, 11), (10, 10)]
+                .into_iter()
+                .collect();
+            assert_eq!(&[(1, 8), (10, 10)], set.spans());
+            assert_eq!(9, set.len());
+            set.remove(4);
+            set.remove(10);
+            set.remove(1);
+            assert_eq!(&[(2, 3), (5, 8)], set.spans());
+            assert_eq!(&[(0, 1), (4, 4), (9, 9)], set.complement(0, 9).spans());
+            set.insert(4, 4);
+            assert_eq!(&[(2, 8)], set.spans());
+            assert!(IntervalSet::new().complement(1, 0).spans().is_empty());
+
+            let mut edges: IntervalSet = [(isize::MIN, isize::MIN + 2), (isize::MAX - 1, isize::MAX)]
+                .into_iter()
+                .collect();
+            edges.remove(isize::MIN);
+            edges.remove(isize::MAX);
+            assert_eq!(&[(isize::MIN + 1, isize::MIN + 2), (isize::MAX - 1, isize::MAX - 1)], edges.spans());
+            edges.insert(isize::MAX, isize::MAX);
+            assert_eq!(&[(isize::MAX - 3, isize::MAX - 2)], edges.complement(isize::MAX - 3, isize::MAX).spans());
+
+            // one more than u64 holds, so it stops at the most it can say
+            let all: IntervalSet = [(isize::MIN, isize::MAX)].into_iter().collect();
+            assert_eq!(u64::MAX, all.len());
+            let halves: IntervalSet = [(isize::MIN, -2), (0, isize::MAX)].into_iter().collect();
+            assert_eq!(u64::MAX, halves.len());
+        }
+
+        // The same operations, on an interval set and on a plain set of its points
+        #[derive(Debug, Clone)]
+        enum Op {
+            Insert(isize, isize),
+            Remove(isize),
+        }
+
+        fn ops() -> impl Strategy<Value = Vec<Op>> {
+            let op = prop_oneof![
+                (-40..40isize, 0..15isize).prop_map(|(s, len)| Op::Insert(s, s + len - 2)),
+                (-40..40isize).prop_map(Op::Remove),
+            ];
+            prop::collection::vec(op, 0..30)
+        }
+
+        fn build(ops: &[Op]) -> (IntervalSet, BTreeSet<isize>) {
+            let mut set = IntervalSet::new();
+            let mut model = BTreeSet::new();
+            for op in ops {
+                match *op {
+                    Op::Insert(s, e) => {
+                        set.insert(s, e);
+                        model.extend(s..=e);
+                    }
+                    Op::Remove(p) => {
+                        set.remove(p);
+                        model.remove(&p);
+                    }
+                }
+            }
+            (set, model)
+        }
+
+        fn points(set: &IntervalSet) -> BTreeSet<isize> {
+            set.spans().iter().flat_map(|&(s, e)| s..=e).collect()
+        }
+
+        proptest! {
+            #[test]
+            fn interval_set_normalised(ops in ops()) {
+                let (set, model) = build(&ops);
+                for w in set.spans().windows(2) {
+                    // sorted, with at least one point between spans
+                    prop_assert!(w[0].1 + 1 < w[1].0);
+                }
+                prop_assert!(set.spans().iter().all(|(s, e)| s <= e));
+                prop_assert_eq!(&model, &points(&set));
+                prop_assert_eq!(model.len() as u64, set.len());
+            }
+
+            #[test]
+            fn interval_set_operations(a in ops(), b in ops(), start in -50..50isize, len in 0..60isize) {
+                let (a, ma) = build(&a);
+                let (b, mb) = build(&b);
+                let end = start + len - 1;
+                prop_assert_eq!(ma.intersection(&mb).copied().collect::<BTreeSet<_>>(), points(&a.intersection(&b)));
+                let outside: BTreeSet<_> = (start..=end).filter(|p| !ma.contains(p)).collect();
+                prop_assert_eq!(&outside, &points(&a.complement(start, end)));
+                let collected: IntervalSet = a.spans().iter().chain(b.spans()).copied().collect();
+                prop_assert_eq!(ma.union(&mb).copied().collect::<BTreeSet<_>>(), points(&collected));
+            }
         }
     }
 }
@@ -0,0 +1,73 @@
+// A plan told minute by minute, the way the puzzle tells it.
+
+use super::relief::Plan;
+use super::Tunnels;
+
+// The first traveler is you, and the rest are elephants
+fn traveler(i: usize, count: usize) -> (String, &'static str, &'static str) {
+    match i {
+        0 => ("You".to_string(), "move", "open"),
+        1 if count == 2 => ("The elephant".to_string(), "moves", "opens"),
+        _ => (format!("Elephant {i}"), "moves", "opens"),
+    }
+}
+
+fn listing(names: &[&str]) -> String {
+    match names {
+        [] => String::new(),
+        [one] => one.to_string(),
+        [a, b] => format!("{a} and {b}"),
+        [rest @ .., last] => format!("{}, and {last}", rest.join(", ")),
+    }
+}
+
+impl Tunnels {
+    pub fn narrate(&self, plan: &Plan) -> String {
+        let flow = |name: &str| self.graph[self.valve_nodes[name]];
+        // what each traveler does in each minute, 1-based
+        let mut actions = vec![vec![]; plan.time + 1];
+        let mut opened = vec![];
+        let count = plan.schedules.len();
+        for (i, schedule) in plan.schedules.iter().enumerate() {
+            let (who, moves, opens) = traveler(i, count);
+            let mut pos = self.valve_nodes["AA"];
+            let mut minute = 0;
+            for opening in schedule {
+                let next = self.valve_nodes[&opening.valve];
+                let route = self.routes.get(&(pos, next)).map_or(&[][..], |r| &r[..]);
+                for valve in route {
+                    minute += 1;
+                    actions[minute].push(format!("{who} {moves} to valve {valve}."));
+                }
+                minute += 1;
+                debug_assert_eq!(opening.minute, minute, "{opening:?} is out of step");
+                actions[minute].push(format!("{who} {opens} valve {}.", opening.valve));
+                opened.push((minute, opening.valve.as_str()));
+                pos = next;
+            }
+        }
+        opened.sort_by_key(|&(_, name)| name);
+
+        let mut res = vec![];
+        for (minute, actions) in actions.iter().enumerate().skip(1) {
+            let open: Vec<&str> = opened
+                .iter()
+                .filter(|&&(at, _)| at < minute)
+                .map(|&(_, name)| name)
+                .collect();
+            let releasing: u64 = open.iter().map(|name| flow(name)).sum();
+            let state = match open.len() {
+                0 => "No valves are open.".to_string(),
+                1 => format!("Valve {} is open, releasing {releasing} pressure.", open[0]),
+                _ => format!(
+                    "Valves {} are open, releasing {releasing} pressure.",
+                    listing(&open)
+                ),
+            };
+            let mut lines = vec![format!("== Minute {minute} =="), state];
+            lines.extend(actions.iter().cloned());
+            res.push(lines.join("\n") + "\n");
+        }
+        res.join("\n")
+    }
+}
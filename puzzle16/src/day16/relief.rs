@@ -0,0 +1,221 @@
+// The most pressure travelers can release in the time they have.
+//
+// Only valves with some flow are worth walking to, so each of those gets a bit. A search state
+// is where a traveler stands, how long is left and which valves it opened, and it keeps the most
+// pressure any route to it released. best[mask] is then the most one traveler releases opening
+// the valves in mask. Travelers that open disjoint sets of valves don't get in each other's way,
+// so a team's best is the best way to split the valves between them.
+
+use std::collections::HashMap;
+use std::iter;
+
+use super::Tunnels;
+
+// A valve a traveler opens, at the end of `minute`, and the pressure it releases from then on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Opening {
+    pub valve: String,
+    pub minute: usize,
+    pub pressure: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Plan {
+    pub time: usize,
+    pub pressure: u64,
+    // what each traveler opens, in order
+    pub schedules: Vec<Vec<Opening>>,
+}
+
+// The search keeps a best pressure for every set of valves with flow, 1 << n of them, and
+// splitting those between three or more travelers goes through 3^n pairs of sets. Past 20 that
+// no longer fits in memory or time.
+const MAX_USEFUL: usize = 20;
+
+// Minutes left, where the traveler is and the valves it opened
+type State = (usize, usize, usize);
+
+// The valves with some flow, and the distances between them
+struct Valves {
+    names: Vec<String>,
+    flows: Vec<u64>,
+    // dist[from][to], where `from` is a valve or the start, which is the last row
+    dist: Vec<Vec<Option<usize>>>,
+}
+
+// Every state reached, with the most pressure released getting there and the state before it
+type Reached = HashMap<State, (u64, Option<State>)>;
+
+impl Valves {
+    fn start(&self) -> usize {
+        self.flows.len()
+    }
+
+    // The most pressure a single traveler releases opening exactly the valves in each mask,
+    // and the state it ends in
+    fn best_by_opened(&self, time: usize) -> (Vec<(u64, State)>, Reached) {
+        let first = (time, self.start(), 0);
+        let mut best = vec![(0, first); 1 << self.flows.len()];
+        let mut reached = Reached::from([(first, (0, None))]);
+        // layers[t] are the states with t minutes left
+        let mut layers: Vec<Vec<State>> = vec![vec![]; time + 1];
+        layers[time].push(first);
+        for left in (0..=time).rev() {
+            for state in std::mem::take(&mut layers[left]) {
+                let (_, pos, opened) = state;
+                let (pressure, _) = reached[&state];
+                if pressure > best[opened].0 {
+                    best[opened] = (pressure, state);
+                }
+                for (next, flow) in self.flows.iter().enumerate() {
+                    if opened & 1 << next != 0 {
+                        continue;
+                    }
+                    // walking there and opening it, with some time to spare for it to flow
+                    let Some(cost) = self.dist[pos][next].map(|d| d + 1) else {
+                        continue;
+                    };
+                    if cost >= left {
+                        continue;
+                    }
+                    let remaining = left - cost;
+                    let released = pressure + flow * remaining as u64;
+                    let to = (remaining, next, opened | 1 << next);
+                    match reached.get_mut(&to) {
+                        None => {
+                            reached.insert(to, (released, Some(state)));
+                            layers[remaining].push(to);
+                        }
+                        Some(entry) if released > entry.0 => *entry = (released, Some(state)),
+                        Some(_) => (),
+                    }
+                }
+            }
+        }
+        (best, reached)
+    }
+
+    // The valves opened on the way to `end`
+    fn schedule(&self, reached: &Reached, time: usize, end: State) -> Vec<Opening> {
+        let mut res: Vec<Opening> = iter::successors(Some(end), |s| reached[s].1)
+            .filter(|&(_, pos, _)| pos != self.start())
+            .map(|(left, pos, _)| Opening {
+                valve: self.names[pos].clone(),
+                minute: time - left,
+                pressure: self.flows[pos] * left as u64,
+            })
+            .collect();
+        res.reverse();
+        res
+    }
+}
+
+impl Tunnels {
+    // None if there are more than MAX_USEFUL valves with flow
+    fn useful_valves(&self) -> Option<Valves> {
+        let start = self.valve_nodes["AA"];
+        let mut useful: Vec<_> = self
+            .valve_nodes
+            .iter()
+            .filter(|(_, &ni)| self.graph[ni] > 0)
+            .collect();
+        // by name, so the bits are the same on every run
+        useful.sort();
+        if useful.len() > MAX_USEFUL {
+            return None;
+        }
+        let nodes: Vec<_> = useful.iter().map(|(_, &ni)| ni).collect();
+        let dist = nodes
+            .iter()
+            .chain([&start])
+            .map(|&from| {
+                nodes
+                    .iter()
+                    .map(|&to| {
+                        if from == to {
+                            Some(0)
+                        } else {
+                            self.graph.find_edge(from, to).map(|e| self.graph[e])
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        Some(Valves {
+            names: useful.iter().map(|(name, _)| name.to_string()).collect(),
+            flows: nodes.iter().map(|&ni| self.graph[ni]).collect(),
+            dist,
+        })
+    }
+
+    // None if there are too many valves with flow to search
+    pub fn optimal_relief(&self, num_travelers: usize, time: usize) -> Option<Plan> {
+        let valves = self.useful_valves()?;
+        let (mut best, reached) = valves.best_by_opened(time);
+        at_most(&mut best);
+        let pressures: Vec<u64> = best.iter().map(|&(p, _)| p).collect();
+        let (pressure, shares) = split(&pressures, num_travelers);
+        Some(Plan {
+            time,
+            pressure,
+            schedules: shares
+                .into_iter()
+                .map(|mask| valves.schedule(&reached, time, best[mask].1))
+                .collect(),
+        })
+    }
+}
+
+// Makes best[mask] the best over all the subsets of mask, as nobody has to open every valve
+// they were given
+fn at_most<T: Copy>(best: &mut [(u64, T)]) {
+    let bits = best.len().trailing_zeros();
+    for bit in 0..bits {
+        for mask in 0..best.len() {
+            if mask & 1 << bit != 0 && best[mask ^ 1 << bit].0 > best[mask].0 {
+                best[mask] = best[mask ^ 1 << bit];
+            }
+        }
+    }
+}
+
+// Every subset of mask, mask itself first and the empty set last
+fn submasks(mask: usize) -> impl Iterator<Item = usize> {
+    iter::successors(Some(mask), move |&s| (s > 0).then(|| (s - 1) & mask))
+}
+
+// The most `travelers` release together, when each opens a different part of the valves, and
+// the part each of them gets
+fn split(best: &[u64], travelers: usize) -> (u64, Vec<usize>) {
+    if travelers == 0 {
+        return (0, vec![]);
+    }
+    let all = best.len() - 1;
+    // the most the team so far releases opening only valves in mask, and the newest member's part
+    let join = |team: &[(u64, usize)], mask: usize| {
+        submasks(mask)
+            .map(|s| (team[s].0 + best[mask ^ s], mask ^ s))
+            .fold((0, 0), |acc, cur| if cur.0 > acc.0 { cur } else { acc })
+    };
+    let mut teams: Vec<Vec<(u64, usize)>> =
+        vec![best.iter().enumerate().map(|(m, &p)| (p, m)).collect()];
+    for _ in 2..travelers {
+        let team = teams.last().unwrap();
+        let bigger = (0..=all).map(|mask| join(team, mask)).collect();
+        teams.push(bigger);
+    }
+    let (total, last) = match travelers {
+        1 => teams[0][all],
+        // the last one only has to take everything the others left
+        _ => join(teams.last().unwrap(), all),
+    };
+    let mut shares = vec![last];
+    let mut rest = all ^ last;
+    for team in teams.iter().rev().take(travelers - 1) {
+        let (_, share) = team[rest];
+        shares.push(share);
+        rest ^= share;
+    }
+    shares.reverse();
+    (total, shares)
+}
@@ -3,57 +3,164 @@ extern crate pest;
 extern crate pest_derive;
 
 fn main() {
+    // `puzzle16 narrate [travelers]` tells how the best plan goes instead, minute by minute
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(report) = day16::report(INPUT, &args) {
+        print!("{report}");
+        return;
+    }
+
     for func in [day16::part1, day16::part2] {
         let start = std::time::Instant::now();
         let res = func(INPUT);
         let dur = start.elapsed().as_nanos();
 
-        println!("{res} [{dur} ns]");
+        match res {
+            Some(pressure) => println!("{pressure} [{dur} ns]"),
+            None => println!("too many valves with flow to search [{dur} ns]"),
+        }
     }
 }
 
 mod day16 {
     use std::collections::HashMap;
+    use std::fmt;
 
+    use pest::error::LineColLocation;
     #[allow(unused)]
     use pest::Parser;
     use petgraph::prelude::*;
 
-    pub fn part1(input: &str) -> u64 {
-        let valves = parse_valves(input);
+    mod narrate;
+    mod relief;
+
+    pub fn part1(input: &str) -> Option<u64> {
+        let valves = parse_valves(input).unwrap_or_else(|e| panic!("bad valve report: {e}"));
+        let tunnels = Tunnels::build(valves);
+        tunnels.optimal_relief(1, 30).map(|plan| plan.pressure)
+    }
+
+    pub fn part2(input: &str) -> Option<u64> {
+        let valves = parse_valves(input).unwrap_or_else(|e| panic!("bad valve report: {e}"));
         let tunnels = Tunnels::build(valves);
-        dbg!(&tunnels);
-        // tunnels.optimal_single(30)
-        tunnels.optimal_relief(1, 30)
+        tunnels.optimal_relief(2, 26).map(|plan| plan.pressure)
     }
 
-    pub fn part2(input: &str) -> u64 {
-        let valves = parse_valves(input);
+    // The best plan for some travelers, told the way the puzzle tells it. Alone you have 30
+    // minutes, and 26 once you've taught the elephants.
+    pub fn report(input: &str, args: &[String]) -> Option<String> {
+        let (cmd, rest) = args.split_first()?;
+        if cmd != "narrate" {
+            return None;
+        }
+        let travelers = rest.first().and_then(|n| n.parse().ok()).unwrap_or(1);
+        let time = if travelers > 1 { 26 } else { 30 };
+        let valves = parse_valves(input).unwrap_or_else(|e| panic!("bad valve report: {e}"));
         let tunnels = Tunnels::build(valves);
-        dbg!(&tunnels);
-        // tunnels.optimal_pair(26)
-        tunnels.optimal_relief(2, 26)
+        let story = match tunnels.optimal_relief(travelers, time) {
+            Some(plan) => tunnels.narrate(&plan),
+            None => "too many valves with flow to search\n".to_string(),
+        };
+        Some(story)
+    }
+
+    // Lines are 1-based
+    #[derive(Debug, PartialEq, Eq)]
+    enum InputError {
+        // col is where the line stopped making sense, 1-based
+        Syntax {
+            line: usize,
+            col: usize,
+        },
+        Duplicate {
+            name: String,
+            line: usize,
+            first: usize,
+        },
+        // A tunnel to a valve that isn't listed
+        Dangling {
+            name: String,
+            line: usize,
+        },
+        NoStart,
     }
 
-    fn parse_valves(input: &str) -> Vec<Valve> {
+    impl fmt::Display for InputError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                InputError::Syntax { line, col } => {
+                    write!(f, "line {line} doesn't read as a valve from column {col}")
+                }
+                InputError::Duplicate { name, line, first } => {
+                    write!(
+                        f,
+                        "valve {name} on line {line} was already listed on line {first}"
+                    )
+                }
+                InputError::Dangling { name, line } => {
+                    write!(
+                        f,
+                        "line {line} has a tunnel to valve {name}, which isn't listed"
+                    )
+                }
+                InputError::NoStart => write!(f, "there's no valve AA to start from"),
+            }
+        }
+    }
+
+    fn parse_valves(input: &str) -> Result<Vec<Valve>, InputError> {
         let mut valves = vec![];
-        for l in input.lines() {
-            let mut valve_entry = ValveParser::parse(Rule::valve, l)
-                .unwrap()
+        let mut lines: HashMap<&str, usize> = HashMap::new();
+        for (i, l) in input
+            .lines()
+            .enumerate()
+            .filter(|(_, l)| !l.trim().is_empty())
+        {
+            let line = i + 1;
+            let syntax = |col| InputError::Syntax { line, col };
+            let mut valve_entry = ValveParser::parse(Rule::valve, l.trim_end())
+                .map_err(|e| match e.line_col {
+                    LineColLocation::Pos((_, col)) | LineColLocation::Span((_, col), _) => {
+                        syntax(col)
+                    }
+                })?
                 .next()
                 .unwrap()
                 .into_inner();
             let valve_name = valve_entry.next().unwrap().as_str();
-            let flow_rate = valve_entry.next().unwrap().as_str().parse::<u64>().unwrap();
+            let flow_entry = valve_entry.next().unwrap();
+            let flow_rate = flow_entry
+                .as_str()
+                .parse::<u64>()
+                .map_err(|_| syntax(flow_entry.as_span().start() + 1))?;
             let lead_to = valve_entry
                 .next()
                 .unwrap()
                 .into_inner()
                 .map(|lead| lead.as_str())
                 .collect::<Vec<_>>();
-            valves.push(Valve::new(valve_name, flow_rate, lead_to));
+            if let Some(&first) = lines.get(valve_name) {
+                return Err(InputError::Duplicate {
+                    name: valve_name.to_string(),
+                    line,
+                    first,
+                });
+            }
+            lines.insert(valve_name, line);
+            valves.push((line, Valve::new(valve_name, flow_rate, lead_to)));
         }
-        valves
+        for (line, v) in &valves {
+            if let Some(name) = v.access.iter().find(|n| !lines.contains_key(n.as_str())) {
+                return Err(InputError::Dangling {
+                    name: name.clone(),
+                    line: *line,
+                });
+            }
+        }
+        if !lines.contains_key("AA") {
+            return Err(InputError::NoStart);
+        }
+        Ok(valves.into_iter().map(|(_, v)| v).collect())
     }
 
     #[derive(Parser)]
@@ -81,15 +188,16 @@ mod day16 {
     struct Tunnels {
         graph: DiGraph<u64, usize>,
         valve_nodes: HashMap<String, NodeIndex>,
+        // once reduced, the valves walked through from one node to another, ending at the other
+        routes: HashMap<(NodeIndex, NodeIndex), Vec<String>>,
     }
 
-    type Path = Vec<NodeIndex>;
-
     impl Tunnels {
         fn build(valves: Vec<Valve>) -> Self {
             let mut tunnels = Self {
                 graph: DiGraph::new(),
                 valve_nodes: HashMap::new(),
+                routes: HashMap::new(),
             };
 
             tunnels.add_valves(&valves);
@@ -115,11 +223,13 @@ mod day16 {
         }
 
         fn reduce(&self) -> Self {
-            use petgraph::algo::floyd_warshall;
-            let assp = floyd_warshall(&self.graph, |_| 1).unwrap();
+            use petgraph::algo::astar;
+            let names: HashMap<NodeIndex, &String> =
+                self.valve_nodes.iter().map(|(n, ni)| (*ni, n)).collect();
             let mut reduced = Self {
                 graph: DiGraph::new(),
                 valve_nodes: HashMap::new(),
+                routes: HashMap::new(),
             };
             for (name, ni) in self.valve_nodes.iter() {
                 let flow = self.graph.node_weight(*ni).unwrap();
@@ -135,99 +245,275 @@ mod day16 {
                     }
                     let orig_src_ni = self.valve_nodes[src_name];
                     let orig_dst_ni = self.valve_nodes[dst_name];
-                    if let Some(path_len) = assp.get(&(orig_src_ni, orig_dst_ni)) {
-                        reduced.graph.add_edge(*src_ni, *dst_ni, *path_len as usize);
+                    let route = astar(&self.graph, orig_src_ni, |n| n == orig_dst_ni, |_| 1, |_| 0);
+                    if let Some((path_len, path)) = route {
+                        reduced.graph.add_edge(*src_ni, *dst_ni, path_len);
+                        let walk = path[1..].iter().map(|ni| names[ni].clone()).collect();
+                        reduced.routes.insert((*src_ni, *dst_ni), walk);
                     }
                 }
             }
             reduced
         }
+    }
 
-        fn optimal_relief(&self, num_travelers: usize, time: usize) -> u64 {
-            let aa = self.valve_nodes["AA"];
-            let mut travelers = vec![Traveler::new(aa, time); num_travelers];
-            self.max_relief(&mut travelers, 0)
+    #[cfg(test)]
+    mod tests {
+        use super::{parse_valves, InputError};
+
+        #[test]
+        fn sample_part1() {
+            const TEST_INPUT: &str = include_str!("example.txt");
+            assert_eq!(Some(1651), super::part1(TEST_INPUT))
         }
 
-        fn max_relief(&self, travelers: &mut Vec<Traveler>, next_traveler: usize) -> u64 {
-            let nt = &travelers[next_traveler];
-            let prev = *nt.path.last().unwrap();
-            let remaining = nt.remaining;
-            let mut max_pressure: u64 = travelers.iter().map(|t| t.pressure).sum();
+        #[test]
+        fn real_part1() {
+            assert_eq!(Some(1986), super::part1(crate::INPUT));
+        }
 
-            for e in self.graph.edges_directed(prev, Direction::Outgoing) {
-                let duration = *e.weight() + 1;
-                if duration >= remaining {
-                    continue;
-                }
-                let next = e.target();
-                if travelers.iter().any(|t| t.path.contains(&next)) {
-                    continue;
-                }
-                let added_pressure = *self.graph.node_weight(next).unwrap();
+        #[test]
+        fn sample_part2() {
+            const TEST_INPUT: &str = include_str!("example.txt");
+            assert_eq!(Some(1707), super::part2(TEST_INPUT))
+        }
 
-                travelers[next_traveler].push(next, duration, added_pressure);
-                max_pressure = max_pressure
-                    .max(self.max_relief(travelers, (next_traveler + 1) % travelers.len()));
-                travelers[next_traveler].pop(duration, added_pressure);
-            }
+        #[test]
+        fn real_part2() {
+            assert_eq!(Some(2464), super::part2(crate::INPUT));
+        }
 
-            max_pressure
+        #[test]
+        fn teams() {
+            const TEST_INPUT: &str = include_str!("example.txt");
+            let tunnels = super::Tunnels::build(super::parse_valves(TEST_INPUT).unwrap());
+            assert_eq!(0, tunnels.optimal_relief(0, 26).unwrap().pressure);
+            assert_eq!(0, tunnels.optimal_relief(3, 2).unwrap().pressure);
+            assert_eq!(1327, tunnels.optimal_relief(1, 26).unwrap().pressure);
+            assert_eq!(1707, tunnels.optimal_relief(2, 26).unwrap().pressure);
+            assert_eq!(1794, tunnels.optimal_relief(3, 26).unwrap().pressure);
+            // one valve each, straight from the start
+            assert_eq!(1830, tunnels.optimal_relief(6, 26).unwrap().pressure);
+
+            let tunnels = super::Tunnels::build(super::parse_valves(crate::INPUT).unwrap());
+            assert_eq!(3030, tunnels.optimal_relief(3, 26).unwrap().pressure);
         }
-    }
 
-    #[derive(Clone)]
-    struct Traveler {
-        path: Path,         // current path
-        remaining: usize,   // remaining time
-        pressure: u64,      // relieved pressure
-    }
+        #[test]
+        fn too_many_valves() {
+            // AA in the middle, with a tunnel to each of `n` valves of flow 1
+            let star = |n| {
+                let names: Vec<String> = ('B'..='C')
+                    .flat_map(|a| ('A'..='Z').map(move |b| format!("{a}{b}")))
+                    .take(n)
+                    .collect();
+                let mut input = format!(
+                    "Valve AA has flow rate=0; tunnels lead to valves {}\n",
+                    names.join(", ")
+                );
+                for name in &names {
+                    input += &format!("Valve {name} has flow rate=1; tunnel leads to valve AA\n");
+                }
+                input
+            };
+            // just time to open one
+            let tunnels = super::Tunnels::build(parse_valves(&star(20)).unwrap());
+            assert_eq!(Some(2), tunnels.optimal_relief(1, 4).map(|p| p.pressure));
 
-    impl Traveler {
-        fn new(start: NodeIndex, time: usize) -> Self {
-            Self {
-                path: vec![start],
-                remaining: time,
-                pressure: 0,
-                // steps: vec![],
+            let input = star(21);
+            let tunnels = super::Tunnels::build(parse_valves(&input).unwrap());
+            assert_eq!(None, tunnels.optimal_relief(1, 30));
+            assert_eq!(None, super::part2(&input));
+        }
+
+        #[test]
+        fn parse_errors() {
+            let valves = parse_valves(
+                "\
+Valve AA has flow rate=0; tunnels lead to valves BB
+Valve BB has flow rate=3; tunnel leads to valve AA
+
+",
+            )
+            .unwrap();
+            assert_eq!(vec!["BB"], valves[0].access);
+            assert_eq!(3, valves[1].flow);
+
+            let syntax = |line, col| Err(InputError::Syntax { line, col });
+            let errors = [
+                (
+                    "Valve AA has flow rate=0; tunnels lead to valve BB",
+                    syntax(1, 27),
+                ),
+                (
+                    "Valve AA has flow rate=0; tunnel leads to valves BB",
+                    syntax(1, 27),
+                ),
+                (
+                    "Valve AA has flow rate=0; tunnel leads to valve BB, CC",
+                    syntax(1, 51),
+                ),
+                (
+                    "Valve AA has flow rate=x; tunnel leads to valve BB",
+                    syntax(1, 24),
+                ),
+                (
+                    "\nValve AA has flow rate=99999999999999999999; tunnel leads to valve AA",
+                    syntax(2, 24),
+                ),
+                (
+                    "Valve A has flow rate=0; tunnel leads to valve BB",
+                    syntax(1, 7),
+                ),
+            ];
+            for (input, err) in errors {
+                assert_eq!(err, parse_valves(input).map(|_| ()), "{input}");
             }
+
+            let duplicate = "\
+Valve AA has flow rate=0; tunnel leads to valve BB
+Valve BB has flow rate=1; tunnel leads to valve AA
+Valve AA has flow rate=2; tunnel leads to valve BB";
+            assert_eq!(
+                Err(InputError::Duplicate {
+                    name: "AA".to_string(),
+                    line: 3,
+                    first: 1
+                }),
+                parse_valves(duplicate).map(|_| ())
+            );
+            let dangling = "\
+Valve AA has flow rate=0; tunnel leads to valve BB
+Valve BB has flow rate=1; tunnels lead to valves AA, CC";
+            assert_eq!(
+                Err(InputError::Dangling {
+                    name: "CC".to_string(),
+                    line: 2
+                }),
+                parse_valves(dangling).map(|_| ())
+            );
+            let no_start = "\
+Valve BB has flow rate=1; tunnel leads to valve CC
+Valve CC has flow rate=1; tunnel leads to valve BB";
+            assert_eq!(Err(InputError::NoStart), parse_valves(no_start).map(|_| ()));
+
+            assert_eq!(
+                "valve BB on line 3 was already listed on line 2",
+                InputError::Duplicate {
+                    name: "BB".to_string(),
+                    line: 3,
+                    first: 2
+                }
+                .to_string()
+            );
+            let message = parse_valves(no_start).map(|_| ()).unwrap_err().to_string();
+            assert_eq!("there's no valve AA to start from", message);
         }
 
-        fn push(&mut self, next: NodeIndex, duration: usize, pressure: u64) {
-            self.path.push(next);
-            self.remaining -= duration;
-            self.pressure += pressure * self.remaining as u64;
+        fn opened(plan: &super::relief::Plan) -> Vec<Vec<(&str, usize)>> {
+            plan.schedules
+                .iter()
+                .map(|s| s.iter().map(|o| (o.valve.as_str(), o.minute)).collect())
+                .collect()
         }
 
-        fn pop(&mut self, duration: usize, pressure: u64) {
-            self.pressure -= pressure * self.remaining as u64;
-            self.remaining += duration;
-            self.path.pop();
+        // The pressure released in every minute of the story, added up
+        fn narrated_pressure(story: &str) -> u64 {
+            story
+                .lines()
+                .filter_map(|l| l.split("releasing ").nth(1))
+                .map(|l| l.trim_end_matches(" pressure.").parse::<u64>().unwrap())
+                .sum()
         }
-    }
 
-    #[cfg(test)]
-    mod tests {
         #[test]
-        fn sample_part1() {
+        fn schedules() {
             const TEST_INPUT: &str = include_str!("example.txt");
-            assert_eq!(1651, super::part1(TEST_INPUT))
-        }
+            let tunnels = super::Tunnels::build(super::parse_valves(TEST_INPUT).unwrap());
 
-        #[test]
-        fn real_part1() {
-            assert_eq!(1986, super::part1(crate::INPUT));
+            let alone = tunnels.optimal_relief(1, 30).unwrap();
+            let route = [
+                ("DD", 2),
+                ("BB", 5),
+                ("JJ", 9),
+                ("HH", 17),
+                ("EE", 21),
+                ("CC", 24),
+            ];
+            assert_eq!(vec![route.to_vec()], opened(&alone));
+            let hh = &alone.schedules[0][3];
+            assert_eq!(22 * 13, hh.pressure);
+            let total: u64 = alone.schedules[0].iter().map(|o| o.pressure).sum();
+            assert_eq!(alone.pressure, total);
+
+            let pair = tunnels.optimal_relief(2, 26).unwrap();
+            assert_eq!(
+                vec![
+                    vec![("JJ", 3), ("BB", 7), ("CC", 9)],
+                    vec![("DD", 2), ("HH", 7), ("EE", 11)]
+                ],
+                opened(&pair)
+            );
+            assert_eq!(
+                vec![Vec::<(&str, usize)>::new()],
+                opened(&tunnels.optimal_relief(1, 2).unwrap())
+            );
+            assert!(tunnels.optimal_relief(0, 30).unwrap().schedules.is_empty());
         }
 
         #[test]
-        fn sample_part2() {
+        fn narration() {
             const TEST_INPUT: &str = include_str!("example.txt");
-            assert_eq!(1707, super::part2(TEST_INPUT))
-        }
+            let tunnels = super::Tunnels::build(super::parse_valves(TEST_INPUT).unwrap());
 
-        #[test]
-        fn real_part2() {
-            assert_eq!(2464, super::part2(crate::INPUT));
+            let alone = tunnels.optimal_relief(1, 30).unwrap();
+            let story = tunnels.narrate(&alone);
+            assert!(story.starts_with(
+                "\
+== Minute 1 ==
+No valves are open.
+You move to valve DD.
+
+== Minute 2 ==
+No valves are open.
+You open valve DD.
+
+== Minute 3 ==
+Valve DD is open, releasing 20 pressure.
+"
+            ));
+            assert!(story.ends_with(
+                "\
+== Minute 30 ==
+Valves BB, CC, DD, EE, HH, and JJ are open, releasing 81 pressure.
+"
+            ));
+            assert_eq!(1651, narrated_pressure(&story));
+
+            let pair = tunnels.optimal_relief(2, 26).unwrap();
+            let story = tunnels.narrate(&pair);
+            assert!(story.contains(
+                "\
+== Minute 2 ==
+No valves are open.
+You move to valve JJ.
+The elephant opens valve DD.
+"
+            ));
+            assert_eq!(1707, narrated_pressure(&story));
+
+            let team = tunnels.optimal_relief(3, 26).unwrap();
+            let story = tunnels.narrate(&team);
+            assert!(story.contains("Elephant 2 opens valve BB."));
+            assert_eq!(1794, narrated_pressure(&story));
+
+            let args = |a: &[&str]| a.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+            assert_eq!(None, super::report(TEST_INPUT, &[]));
+            assert_eq!(None, super::report(TEST_INPUT, &args(&["part1"])));
+            let alone = super::report(TEST_INPUT, &args(&["narrate"])).unwrap();
+            assert_eq!(1651, narrated_pressure(&alone));
+            let pair = super::report(TEST_INPUT, &args(&["narrate", "2"])).unwrap();
+            assert_eq!(1707, narrated_pressure(&pair));
         }
     }
 }